@@ -2,3 +2,12 @@ pub const TITLE: &str = "deasil";
 pub const ZOOM_FACTOR: f64 = 1.01;
 pub const DEFAULT_ZOOM: f64 = 20.0;
 pub const WINDOW_SIZE: [u32; 2] = [1000, 800];
+pub const TICK_RATE: f64 = 60.0;
+pub const MAX_TICKS_PER_UPDATE: u32 = 5;
+pub const WAVES_PATH: &str = "assets/waves.ron";
+pub const PREFABS_PATH: &str = "assets/prefabs.ron";
+pub const SPRITES_PATH: &str = "assets/sprites";
+pub const BINDINGS_PATH: &str = "assets/bindings.ron";
+pub const SAVE_PATH: &str = "save.ron";
+pub const REPLAY_PATH: &str = "replay.ron";
+pub const SCREENSHOT_PATH: &str = "screenshot.png";
@@ -0,0 +1,242 @@
+use deasil::game;
+use deasil::game::components::*;
+use deasil::game::data;
+use deasil::game::replay::{self, Recording};
+
+use specs::*;
+use std::process;
+use std::str::FromStr;
+
+const USAGE: &str = "usage: deasil --frontend headless [--ticks N] [--seed N] \
+                     [--script FILE | --replay FILE] [--events] [--report N] \
+                     [--draw-list FILE] [--screenshot FILE]";
+
+// how many ticks to run without a script or a replay, 10 s at the default tick rate
+const DEFAULT_TICKS: u32 = 600;
+
+struct Options {
+    ticks: Option<u32>,
+    seed: Option<u64>,
+    script: Option<String>,
+    replay: Option<String>,
+
+    // print every event as it happens
+    print_events: bool,
+
+    // print statistics every this many ticks, 0 = only at the end
+    report_every: u32,
+
+    // where to save what the last tick would draw, see `render::DrawList`
+    draw_list: Option<String>,
+
+    // where to save a picture of the last tick
+    screenshot: Option<String>,
+}
+
+// scripted input, `input` is held for `ticks` ticks
+// missing input fields are not pressed
+#[derive(Deserialize)]
+struct ScriptStep {
+    ticks: u32,
+    input: Input,
+}
+
+#[derive(Default)]
+struct EventTotals {
+    spawned: usize,
+    waves: usize,
+    fired: usize,
+    hits: usize,
+    destroyed: usize,
+}
+
+impl EventTotals {
+    fn count(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Spawned { .. } => self.spawned += 1,
+            GameEvent::WaveStarted { .. } => self.waves += 1,
+            GameEvent::Fired { .. } => self.fired += 1,
+            GameEvent::Hit { .. } => self.hits += 1,
+            GameEvent::Destroyed { .. } => self.destroyed += 1,
+        }
+    }
+}
+
+// runs the game without a window, `args` are the command line arguments after the frontend choice
+pub fn main(args: &[String]) {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let recording = options
+        .replay
+        .as_ref()
+        .map(|path| load_or_exit(Recording::load(path), path));
+    let inputs: Vec<Input> = match (&recording, &options.script) {
+        (Some(recording), _) => recording.inputs().cloned().collect(),
+        (None, Some(path)) => load_or_exit(data::load::<Vec<ScriptStep>, _>(path), path)
+            .into_iter()
+            .flat_map(|step| (0..step.ticks).map(move |_| step.input.clone()))
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+    let ticks = match options.ticks {
+        Some(ticks) => ticks,
+        None if inputs.is_empty() => DEFAULT_TICKS,
+        None => inputs.len() as u32,
+    };
+
+    let mut game = game::Game::new();
+    if let Some(recording) = &recording {
+        game.set_seed(recording.seed);
+        game.set_timestep(game::Timestep {
+            tick_rate: recording.tick_rate,
+            ..game::Timestep::default()
+        });
+    } else if let Some(seed) = options.seed {
+        game.set_seed(seed);
+    }
+    game.start();
+    println!("Seed: {}", game.seed());
+
+    let mut totals = EventTotals::default();
+    for tick in 0..ticks {
+        // nothing is pressed once the script or the replay runs out
+        *game.input_mut() = inputs.get(tick as usize).cloned().unwrap_or_default();
+        game.tick();
+
+        for event in game.events() {
+            totals.count(event);
+            if options.print_events {
+                println!("{:>6} {:?}", tick, event);
+            }
+        }
+        if options.report_every > 0 && (tick + 1) % options.report_every == 0 {
+            report(&game, tick + 1);
+        }
+    }
+
+    report(&game, ticks);
+    println!(
+        "Events: {} spawned, {} waves, {} fired, {} hits, {} destroyed",
+        totals.spawned, totals.waves, totals.fired, totals.hits, totals.destroyed
+    );
+    let hash = replay::world_hash(&game.world);
+    println!("World hash: {}", hash);
+
+    if let Some(path) = &options.draw_list {
+        match game.draw_list().save(path) {
+            Ok(_) => println!("Saved the draw list to {}", path),
+            Err(e) => println!("Failed to save the draw list to {}: {}", path, e),
+        }
+    }
+    if let Some(path) = &options.screenshot {
+        match game.screenshot(path) {
+            Ok(_) => println!("Saved a screenshot to {}", path),
+            Err(e) => println!("Failed to save a screenshot to {}: {}", path, e),
+        }
+    }
+
+    // only a whole replay ends up in the recorded world
+    if let Some(recording) = recording {
+        if ticks == recording.ticks() {
+            match recording.hash {
+                Some(recorded) if recorded == hash => println!("The world matches the recording"),
+                Some(_) => {
+                    println!("The world differs from the recording!");
+                    process::exit(1);
+                }
+                None => println!("The recording has no hash to check against"),
+            }
+        }
+    }
+}
+
+// prints entity counts and the player's state
+fn report(game: &game::Game, tick: u32) {
+    let world = &game.world;
+    let entities = world.entities().join().count();
+    let enemies = world.read_storage::<AiBehaviour>().join().count();
+    let projectiles = world.read_storage::<Projectile>().join().count();
+    let player = match *world.read_resource::<Option<Player>>() {
+        Some(Player(entity)) => {
+            let position = world
+                .read_storage::<Position>()
+                .get(entity)
+                .map_or(Point::default(), |p| p.0);
+            let hit_points = world
+                .read_storage::<Health>()
+                .get(entity)
+                .map_or(0.0, |h| h.hit_points);
+            format!(
+                "player at ({:.2}, {:.2}) with {} hp",
+                position.x, position.y, hit_points
+            )
+        }
+        None => "no player".to_string(),
+    };
+    println!(
+        "tick {} ({:.2} s): {} entities, {} enemies, {} projectiles, {}",
+        tick,
+        world.read_resource::<Clock>().time,
+        entities,
+        enemies,
+        projectiles,
+        player
+    );
+}
+
+fn load_or_exit<T>(result: Result<T, data::DataError>, path: &str) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Failed to load {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        ticks: None,
+        seed: None,
+        script: None,
+        replay: None,
+        print_events: false,
+        report_every: 0,
+        draw_list: None,
+        screenshot: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ticks" => options.ticks = Some(parse_value(arg, args.next())?),
+            "--seed" => options.seed = Some(parse_value(arg, args.next())?),
+            "--script" => options.script = Some(parse_value(arg, args.next())?),
+            "--replay" => options.replay = Some(parse_value(arg, args.next())?),
+            "--events" => options.print_events = true,
+            "--report" => options.report_every = parse_value(arg, args.next())?,
+            "--draw-list" => options.draw_list = Some(parse_value(arg, args.next())?),
+            "--screenshot" => options.screenshot = Some(parse_value(arg, args.next())?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    if options.script.is_some() && options.replay.is_some() {
+        return Err("--script and --replay can't be used together".to_string());
+    }
+    if options.seed.is_some() && options.replay.is_some() {
+        return Err("--seed can't be used with --replay, it has its own".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_value<T: FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", option, value))
+}
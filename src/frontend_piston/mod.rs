@@ -1,19 +1,18 @@
 extern crate glutin_window;
-extern crate graphics;
 extern crate piston;
 
 mod rendering;
 
-use consts;
-use game;
+use deasil::consts;
+use deasil::game;
+use deasil::game::bindings::{self, Bindings, Control, Controls, GamepadAxis, GamepadButton};
 
 use self::glutin_window::GlutinWindow as Window;
-use self::graphics::Viewport;
 use self::piston::event_loop::*;
 use self::piston::input::*;
 use self::piston::window::WindowSettings;
 
-fn handle_input_event(game: &mut game::Game, e: &Event) {
+fn handle_input_event(game: &mut game::Game, controls: &mut Controls, e: &Event) {
     let mut input = game.input_mut();
     if let Some(m) = e.mouse_cursor_args() {
         input.mouse_position = m;
@@ -21,31 +20,41 @@ fn handle_input_event(game: &mut game::Game, e: &Event) {
     if let Some(u) = e.mouse_scroll_args() {
         input.mouse_scroll = u;
     }
-    if let Some(k) = e.press_args() {
-        match k {
-            Button::Mouse(MouseButton::Left) => input.mouse_left = true,
-            Button::Keyboard(Key::W) => input.up = true,
-            Button::Keyboard(Key::S) => input.down = true,
-            Button::Keyboard(Key::A) => input.left = true,
-            Button::Keyboard(Key::D) => input.right = true,
-            _ => {}
+    if let Some(b) = e.press_args().and_then(to_control) {
+        controls.press(b, &mut input);
+    }
+    if let Some(b) = e.release_args().and_then(to_control) {
+        controls.release(&b, &mut input);
+    }
+    if let Some(a) = e.controller_axis_args() {
+        if let Some(axis) = GamepadAxis::from_index(a.axis) {
+            controls.move_axis(axis, a.position, &mut input);
         }
     }
-    if let Some(k) = e.release_args() {
-        match k {
-            Button::Mouse(MouseButton::Left) => input.mouse_left = false,
-            Button::Keyboard(Key::W) => input.up = false,
-            Button::Keyboard(Key::S) => input.down = false,
-            Button::Keyboard(Key::A) => input.left = false,
-            Button::Keyboard(Key::D) => input.right = false,
-            _ => {}
+    if let Some(false) = e.focus_args() {
+        controls.release_all(&mut input);
+    }
+}
+
+fn to_control(button: Button) -> Option<Control> {
+    match button {
+        Button::Keyboard(key) => Some(Control::Key(format!("{:?}", key))),
+        Button::Mouse(button) => match button {
+            MouseButton::Left => Some(Control::Mouse(bindings::MouseButton::Left)),
+            MouseButton::Right => Some(Control::Mouse(bindings::MouseButton::Right)),
+            MouseButton::Middle => Some(Control::Mouse(bindings::MouseButton::Middle)),
+            MouseButton::X1 => Some(Control::Mouse(bindings::MouseButton::X1)),
+            MouseButton::X2 => Some(Control::Mouse(bindings::MouseButton::X2)),
+            _ => None,
+        },
+        Button::Controller(button) => {
+            GamepadButton::from_index(button.button).map(Control::GamepadButton)
         }
+        Button::Hat(_) => None,
     }
 }
 
 pub fn main() {
-    use specs::DispatcherBuilder;
-
     let mut window: Window = WindowSettings::new(consts::TITLE, consts::WINDOW_SIZE)
         .opengl(rendering::OPENGL)
         .exit_on_esc(true)
@@ -53,18 +62,38 @@ pub fn main() {
         .unwrap();
 
     let mut game = game::Game::new();
-    game.add_resource(None as Option<graphics::Viewport>);
+    println!("Seed: {}", game.seed());
 
     let mut events = Events::new(EventSettings::new());
-    let mut rendering_dispatcher = DispatcherBuilder::new()
-        .with_thread_local(rendering::RenderSys::default())
-        .build();
-
-    game.create_player();
+    game.start();
+    game.start_recording();
+    let mut renderer = rendering::Renderer::new(&game.sprites());
+    let mut controls = Controls::new(Bindings::load_or_builtin(consts::BINDINGS_PATH));
 
     while let Some(e) = events.next(&mut window) {
         // pass events to game
-        handle_input_event(&mut game, &e);
+        handle_input_event(&mut game, &mut controls, &e);
+
+        // quick save and load
+        match e.press_args() {
+            Some(Button::Keyboard(Key::F5)) => match game.save(consts::SAVE_PATH) {
+                Ok(_) => println!("Saved to {}", consts::SAVE_PATH),
+                Err(e) => println!("Failed to save to {}: {}", consts::SAVE_PATH, e),
+            },
+            Some(Button::Keyboard(Key::F9)) => match game.load(consts::SAVE_PATH) {
+                Ok(_) => println!("Loaded {}", consts::SAVE_PATH),
+                Err(e) => println!("Failed to load {}: {}", consts::SAVE_PATH, e),
+            },
+            Some(Button::Keyboard(Key::F12)) => match game.screenshot(consts::SCREENSHOT_PATH) {
+                Ok(_) => println!("Saved a screenshot to {}", consts::SCREENSHOT_PATH),
+                Err(e) => println!(
+                    "Failed to save a screenshot to {}: {}",
+                    consts::SCREENSHOT_PATH,
+                    e
+                ),
+            },
+            _ => {}
+        }
 
         // update
         if let Some(u) = e.update_args() {
@@ -73,8 +102,19 @@ pub fn main() {
 
         // render
         if let Some(r) = e.render_args() {
-            (*game.write_resource::<Option<Viewport>>()) = Some(r.viewport());
-            game.render(&mut rendering_dispatcher);
+            // the window size without DPI scaling, like the mouse position
+            let viewport = r.viewport();
+            let [width, height] = viewport.window_size;
+            game.input_mut().viewport_size = [width as f64, height as f64];
+            renderer.draw(viewport, &game.draw_list());
+        }
+    }
+
+    // keep the run so it can be replayed
+    if let Some(recording) = game.stop_recording() {
+        match recording.save(consts::REPLAY_PATH) {
+            Ok(_) => println!("Recorded {} ticks to {}", recording.ticks(), consts::REPLAY_PATH),
+            Err(e) => println!("Failed to save recording to {}: {}", consts::REPLAY_PATH, e),
         }
     }
 }
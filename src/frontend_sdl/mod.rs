@@ -2,34 +2,118 @@ extern crate sdl2;
 
 mod rendering;
 
-use consts;
-use game;
+use deasil::consts;
+use deasil::game;
+use deasil::game::bindings::{self, Bindings, Control, Controls, GamepadAxis, GamepadButton};
 
 use std::time::{Duration, Instant};
 
-use self::sdl2::event::Event;
+use self::sdl2::event::{Event, WindowEvent};
 use self::sdl2::keyboard::Keycode;
+use self::sdl2::mouse::{MouseButton, MouseWheelDirection};
 
 fn seconds_from_duration(d: Duration) -> f64 {
     d.as_secs() as f64 + (d.subsec_micros() as f64 / 1_000_000.0)
 }
 
-pub fn main() {
-    use specs::DispatcherBuilder;
+fn handle_input_event(game: &mut game::Game, controls: &mut Controls, event: &Event) {
+    let mut input = game.input_mut();
+    match *event {
+        Event::MouseMotion { x, y, .. } => input.mouse_position = [x as f64, y as f64],
+        Event::Window {
+            win_event: WindowEvent::SizeChanged(width, height),
+            ..
+        } => input.viewport_size = [width as f64, height as f64],
+        Event::Window {
+            win_event: WindowEvent::FocusLost,
+            ..
+        } => controls.release_all(&mut input),
+        Event::MouseWheel {
+            x, y, direction, ..
+        } => {
+            let sign = match direction {
+                MouseWheelDirection::Flipped => -1.0,
+                _ => 1.0,
+            };
+            input.mouse_scroll = [x as f64 * sign, y as f64 * sign];
+        }
+        Event::MouseButtonDown { mouse_btn, .. } => {
+            if let Some(control) = mouse_control(mouse_btn) {
+                controls.press(control, &mut input);
+            }
+        }
+        Event::MouseButtonUp { mouse_btn, .. } => {
+            if let Some(control) = mouse_control(mouse_btn) {
+                controls.release(&control, &mut input);
+            }
+        }
+        Event::KeyDown {
+            keycode: Some(keycode),
+            ..
+        } => controls.press(Control::Key(keycode.name()), &mut input),
+        Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } => controls.release(&Control::Key(keycode.name()), &mut input),
+        Event::ControllerButtonDown { button, .. } => {
+            if let Some(button) = GamepadButton::from_index(button as u8) {
+                controls.press(Control::GamepadButton(button), &mut input);
+            }
+        }
+        Event::ControllerButtonUp { button, .. } => {
+            if let Some(button) = GamepadButton::from_index(button as u8) {
+                controls.release(&Control::GamepadButton(button), &mut input);
+            }
+        }
+        Event::ControllerAxisMotion { axis, value, .. } => {
+            if let Some(axis) = GamepadAxis::from_index(axis as u8) {
+                controls.move_axis(axis, value as f64 / i16::MAX as f64, &mut input);
+            }
+        }
+        // let go of whatever the unplugged gamepad was holding
+        Event::ControllerDeviceRemoved { .. } => controls.release_all(&mut input),
+        _ => {}
+    }
+}
+
+fn mouse_control(button: MouseButton) -> Option<Control> {
+    let button = match button {
+        MouseButton::Left => bindings::MouseButton::Left,
+        MouseButton::Right => bindings::MouseButton::Right,
+        MouseButton::Middle => bindings::MouseButton::Middle,
+        MouseButton::X1 => bindings::MouseButton::X1,
+        MouseButton::X2 => bindings::MouseButton::X2,
+        MouseButton::Unknown => return None,
+    };
+    Some(Control::Mouse(button))
+}
 
+pub fn main() {
     let sdl_context = sdl2::init().unwrap();
     let video_subsys = sdl_context.video().unwrap();
-    let window = video_subsys.window(consts::TITLE, rendering::WINDOW_SIZE[0], rendering::WINDOW_SIZE[1])
+    let window = video_subsys
+        .window(consts::TITLE, consts::WINDOW_SIZE[0], consts::WINDOW_SIZE[1])
         .position_centered()
+        .resizable()
         .opengl()
         .build()
         .unwrap();
 
     let mut game = game::Game::new();
+    println!("Seed: {}", game.seed());
+
+    let canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut renderer = rendering::Renderer::new(canvas);
+
+    game.start();
+    game.start_recording();
+    let mut atlas = rendering::create_atlas_texture(&texture_creator, &game.sprites());
 
-    let mut rendering_dispatcher = DispatcherBuilder::new()
-        .with_thread_local(rendering::RenderSys::new(window.into_canvas().build().unwrap()))
-        .build();
+    let mut controls = Controls::new(Bindings::load_or_builtin(consts::BINDINGS_PATH));
+    let controller_subsys = sdl_context.game_controller().unwrap();
+    // gamepads only send events while open
+    let mut gamepads = Vec::new();
 
     let mut events = sdl_context.event_pump().unwrap();
     let mut last_time = Instant::now();
@@ -39,19 +123,56 @@ pub fn main() {
 
         // pass events to the game
         for event in events.poll_iter() {
-            match event {
-                Event::Quit {..} => break 'running,
+            handle_input_event(&mut game, &mut controls, &event);
 
-                Event::KeyDown {keycode: Some(keycode), ..} => {
-                    if keycode == Keycode::Escape {
-                        break 'running
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+
+                // also sent for gamepads plugged in at start
+                Event::ControllerDeviceAdded { which, .. } => match controller_subsys.open(which) {
+                    Ok(gamepad) => {
+                        println!("Gamepad connected: {}", gamepad.name());
+                        gamepads.push(gamepad);
                     }
+                    Err(e) => println!("Failed to open gamepad {}: {}", which, e),
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    gamepads.retain(|gamepad| gamepad.instance_id() != which)
                 }
 
-                Event::MouseButtonDown {x, y, ..} => {
-                    game.handle_mouse_move([x as f64, y as f64]);
-                    game.handle_mouse_left_click();
-                }
+                // quick save and load
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => match game.save(consts::SAVE_PATH) {
+                    Ok(_) => println!("Saved to {}", consts::SAVE_PATH),
+                    Err(e) => println!("Failed to save to {}: {}", consts::SAVE_PATH, e),
+                },
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => match game.load(consts::SAVE_PATH) {
+                    Ok(_) => println!("Loaded {}", consts::SAVE_PATH),
+                    Err(e) => println!("Failed to load {}: {}", consts::SAVE_PATH, e),
+                },
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => match game.screenshot(consts::SCREENSHOT_PATH) {
+                    Ok(_) => println!("Saved a screenshot to {}", consts::SCREENSHOT_PATH),
+                    Err(e) => println!(
+                        "Failed to save a screenshot to {}: {}",
+                        consts::SCREENSHOT_PATH,
+                        e
+                    ),
+                },
 
                 _ => {}
             }
@@ -62,6 +183,14 @@ pub fn main() {
         last_time = current;
 
         // draw
-        game.render(&mut rendering_dispatcher);
+        renderer.draw(&game.draw_list(), atlas.as_mut());
+    }
+
+    // keep the run so it can be replayed
+    if let Some(recording) = game.stop_recording() {
+        match recording.save(consts::REPLAY_PATH) {
+            Ok(_) => println!("Recorded {} ticks to {}", recording.ticks(), consts::REPLAY_PATH),
+            Err(e) => println!("Failed to save recording to {}: {}", consts::REPLAY_PATH, e),
+        }
     }
 }
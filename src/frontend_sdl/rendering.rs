@@ -1,40 +1,222 @@
-extern crate specs;
-
 use super::sdl2::gfx::primitives::DrawRenderer;
-use super::sdl2::pixels::Color;
-use super::sdl2::video::Window;
-use super::sdl2::render::Canvas;
-use self::specs::*;
+use super::sdl2::pixels::{Color, PixelFormatEnum};
+use super::sdl2::rect::Rect;
+use super::sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use super::sdl2::video::{Window, WindowContext};
 
-use game::components::*;
+use deasil::game::components::{Point, Vector};
+use deasil::game::render::{self, DrawCommand, DrawList, Outline, Primitive, View};
+use deasil::game::sprites::{Region, SpriteAtlas};
 
-pub const WINDOW_SIZE: [u32; 2] = [800, 800];
+// uploads the sprites, `None` when there are none or they can't be uploaded
+pub fn create_atlas_texture<'a>(
+    creator: &'a TextureCreator<WindowContext>,
+    sprites: &SpriteAtlas,
+) -> Option<Texture<'a>> {
+    let image = sprites.image();
+    if image.pixels().is_empty() {
+        return None;
+    }
+    // the bytes of ABGR8888 pixels are in RGBA order on little endian machines
+    let mut texture = match creator.create_texture_static(
+        PixelFormatEnum::ABGR8888,
+        image.width(),
+        image.height(),
+    ) {
+        Ok(texture) => texture,
+        Err(e) => {
+            println!("Failed to create the sprite texture: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = texture.update(None, image.pixels(), image.width() as usize * 4) {
+        println!("Failed to upload the sprites: {}", e);
+        return None;
+    }
+    texture.set_blend_mode(BlendMode::Blend);
+    Some(texture)
+}
 
-pub struct RenderSys {
-    canvas: Canvas<Window>
+// draws the game's draw lists into a window
+pub struct Renderer {
+    canvas: Canvas<Window>,
 }
 
-impl RenderSys {
+impl Renderer {
     pub fn new(canvas: Canvas<Window>) -> Self {
-        RenderSys { canvas }
+        Renderer { canvas }
     }
-}
 
-impl<'a> System<'a> for RenderSys {
-    type SystemData = ReadStorage<'a, Position>;
-
-    fn run(&mut self, pos_storage: Self::SystemData) {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    // `atlas` is the texture from `create_atlas_texture`
+    pub fn draw(&mut self, list: &DrawList, mut atlas: Option<&mut Texture>) {
+        self.canvas.set_draw_color(to_sdl_color(list.background));
         self.canvas.clear();
-        self.canvas.set_draw_color(Color::RGB(255, 0, 0));
-        for pos in (&pos_storage).join() {
-            self.canvas.filled_circle(
-                pos.x as i16,
-                pos.y as i16,
-                2,
-                Color::RGB(255, 0, 0),
-            ).unwrap();
+
+        for command in &list.commands {
+            let color = to_sdl_color(command.color);
+            let result = match command.primitive {
+                Primitive::Circle { radius } => {
+                    let (x, y) = to_screen(&list.view, command.position);
+                    // keep tiny circles like the stars visible
+                    let radius = (radius * list.view.zoom).round().max(1.0);
+                    self.canvas.filled_circle(x, y, radius as i16, color)
+                }
+                Primitive::Rectangle { size } => {
+                    let (vx, vy): (Vec<_>, Vec<_>) =
+                        corners(&list.view, command, size).into_iter().unzip();
+                    self.canvas.filled_polygon(&vx, &vy, color)
+                }
+                Primitive::Sprite { size, region, .. } => match atlas {
+                    Some(ref mut atlas) => {
+                        draw_sprite(&mut self.canvas, atlas, &list.view, region, size, command)
+                    }
+                    None => Ok(()),
+                },
+                Primitive::Polygon {
+                    ref points,
+                    ref triangles,
+                } => {
+                    let points = place_all(&list.view, command, points);
+                    triangles.iter().try_for_each(|&[a, b, c]| {
+                        let (a, b, c) = (points[a], points[b], points[c]);
+                        self.canvas
+                            .filled_trigon(a.0, a.1, b.0, b.1, c.0, c.1, color)
+                    })
+                }
+                Primitive::Polyline { ref points, width } => {
+                    let points = place_all(&list.view, command, points);
+                    let width = line_width(width * list.view.zoom);
+                    points
+                        .iter()
+                        .zip(points.iter().skip(1))
+                        .try_for_each(|(a, b)| {
+                            self.canvas.thick_line(a.0, a.1, b.0, b.1, width, color)
+                        })
+                }
+            }.and_then(|_| match command.outline {
+                Some(outline) => draw_outline(&self.canvas, &list.view, command, outline),
+                None => Ok(()),
+            });
+            if let Err(e) = result {
+                println!("Failed to draw {:?}: {}", command.primitive, e);
+            }
         }
+
         self.canvas.present();
     }
 }
+
+fn to_screen(view: &View, p: Point) -> (i16, i16) {
+    let p = view.to_screen(p);
+    (p.x as i16, p.y as i16)
+}
+
+// screen coords of a rectangle `size` big placed by `command`
+fn corners(view: &View, command: &DrawCommand, size: Vector) -> Vec<(i16, i16)> {
+    let (w, h) = (size.dx / 2.0, size.dy / 2.0);
+    let corners = [
+        Point::new(-w, -h),
+        Point::new(w, -h),
+        Point::new(w, h),
+        Point::new(-w, h),
+    ];
+    place_all(view, command, &corners)
+}
+
+// screen coords of `points` in the coords of the primitive placed by `command`
+fn place_all(view: &View, command: &DrawCommand, points: &[Point]) -> Vec<(i16, i16)> {
+    points
+        .iter()
+        .map(|p| {
+            let offset = Vector::new(p.x, p.y).rotated(command.rotation);
+            to_screen(view, command.position + offset)
+        }).collect()
+}
+
+fn draw_outline(
+    canvas: &Canvas<Window>,
+    view: &View,
+    command: &DrawCommand,
+    outline: Outline,
+) -> Result<(), String> {
+    let width = outline.width * view.zoom;
+    let color = to_sdl_color(outline.color);
+    match command.primitive {
+        Primitive::Circle { radius } => {
+            // rings next to each other, as wide as the line
+            let (x, y) = to_screen(view, command.position);
+            let radius = radius * view.zoom;
+            let width = width.max(1.0);
+            let inner = (radius - width / 2.0).max(0.0).round() as i16;
+            let outer = (radius + width / 2.0).round() as i16;
+            for ring in inner..=outer {
+                canvas.circle(x, y, ring, color)?;
+            }
+            Ok(())
+        }
+        Primitive::Rectangle { size } | Primitive::Sprite { size, .. } => {
+            draw_closed_line(canvas, &corners(view, command, size), width, color)
+        }
+        Primitive::Polygon { ref points, .. } => {
+            draw_closed_line(canvas, &place_all(view, command, points), width, color)
+        }
+        Primitive::Polyline { .. } => Ok(()),
+    }
+}
+
+fn draw_closed_line(
+    canvas: &Canvas<Window>,
+    points: &[(i16, i16)],
+    width: f64,
+    color: Color,
+) -> Result<(), String> {
+    let width = line_width(width);
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .try_for_each(|(a, b)| canvas.thick_line(a.0, a.1, b.0, b.1, width, color))
+}
+
+// in pixels, at least one
+fn line_width(width: f64) -> u8 {
+    width.round().clamp(1.0, 255.0) as u8
+}
+
+fn draw_sprite(
+    canvas: &mut Canvas<Window>,
+    atlas: &mut Texture,
+    view: &View,
+    region: Region,
+    size: Vector,
+    command: &DrawCommand,
+) -> Result<(), String> {
+    let (x, y) = to_screen(view, command.position);
+    let (width, height) = (size.dx * view.zoom, size.dy * view.zoom);
+    let source = Rect::new(
+        region.x as i32,
+        region.y as i32,
+        region.width,
+        region.height,
+    );
+    // turned around its center
+    let target = Rect::new(
+        x as i32 - (width / 2.0) as i32,
+        y as i32 - (height / 2.0) as i32,
+        width as u32,
+        height as u32,
+    );
+    let color = to_sdl_color(command.color);
+    atlas.set_color_mod(color.r, color.g, color.b);
+    atlas.set_alpha_mod(color.a);
+    let angle = command.rotation.to_degrees();
+    canvas.copy_ex(atlas, source, target, angle, None, false, false)
+}
+
+fn to_sdl_color(color: render::Color) -> Color {
+    Color::RGBA(
+        (color[0].min(1.0) * 255.0) as u8,
+        (color[1].min(1.0) * 255.0) as u8,
+        (color[2].min(1.0) * 255.0) as u8,
+        (color[3].min(1.0) * 255.0) as u8,
+    )
+}
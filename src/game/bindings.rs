@@ -0,0 +1,343 @@
+use game::components::Input;
+use game::data;
+use ron;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// bindings compiled into the game, used when the bindings file can't be loaded
+const BUILTIN_BINDINGS: &str = include_str!("../../assets/bindings.ron");
+
+// held actions, see `Input`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+    Secondary,
+    SpawnEnemy,
+    Pause,
+    ZoomIn,
+    ZoomOut,
+}
+
+// actions from -1 to 1, see `Input`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum AnalogAction {
+    ThrustX,
+    ThrustY,
+    AimX,
+    AimY,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+// named after the buttons of an Xbox controller, in the order SDL numbers them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub fn from_index(index: u8) -> Option<Self> {
+        use self::GamepadButton::*;
+        let buttons = [
+            A,
+            B,
+            X,
+            Y,
+            Back,
+            Guide,
+            Start,
+            LeftStick,
+            RightStick,
+            LeftShoulder,
+            RightShoulder,
+            DPadUp,
+            DPadDown,
+            DPadLeft,
+            DPadRight,
+        ];
+        buttons.get(index as usize).cloned()
+    }
+}
+
+// sticks go from -1 to 1, left and up are negative, triggers go from 0 to 1
+// in the order SDL numbers them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub fn from_index(index: u8) -> Option<Self> {
+        use self::GamepadAxis::*;
+        let axes = [LeftX, LeftY, RightX, RightY, LeftTrigger, RightTrigger];
+        axes.get(index as usize).cloned()
+    }
+}
+
+// anything the player can press
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Control {
+    // keys are named the way the frontend names them, letters, F keys, arrows ("Up", ..),
+    // "Space", "Return" and "Escape" have the same names in every frontend
+    Key(String),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButton),
+    // pressed when pushed more than halfway, mostly for triggers
+    GamepadAxis(GamepadAxis),
+}
+
+// which controls trigger which actions, loaded from a RON file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Bindings {
+    // an action is held while any of its controls is
+    #[serde(default)]
+    actions: HashMap<Action, Vec<Control>>,
+    // the values of all axes bound to an action add up
+    #[serde(default)]
+    analog: HashMap<AnalogAction, Vec<GamepadAxis>>,
+    // axis values closer to 0 than this are ignored, sticks never quite center
+    #[serde(default)]
+    dead_zone: f64,
+}
+
+impl Bindings {
+    pub fn builtin() -> Self {
+        ron::de::from_str(BUILTIN_BINDINGS).expect("built-in bindings are broken")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, data::DataError> {
+        data::load(path)
+    }
+
+    // like `load`, but reports failures and falls back to the built-in bindings
+    pub fn load_or_builtin<P: AsRef<Path>>(path: P) -> Self {
+        match Bindings::load(&path) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                println!("Failed to load bindings from {}: {}", path.as_ref().display(), e);
+                Bindings::builtin()
+            }
+        }
+    }
+}
+
+// turns the controls the frontend reports into actions on `Input`
+pub struct Controls {
+    bindings: Bindings,
+    held: HashSet<Control>,
+    axes: HashMap<GamepadAxis, f64>,
+}
+
+impl Controls {
+    pub fn new(bindings: Bindings) -> Self {
+        Controls {
+            bindings,
+            held: HashSet::new(),
+            axes: HashMap::new(),
+        }
+    }
+
+    pub fn press(&mut self, control: Control, input: &mut Input) {
+        self.held.insert(control);
+        self.apply(input);
+    }
+
+    pub fn release(&mut self, control: &Control, input: &mut Input) {
+        self.held.remove(control);
+        self.apply(input);
+    }
+
+    pub fn move_axis(&mut self, axis: GamepadAxis, value: f64, input: &mut Input) {
+        self.axes.insert(axis, value);
+        self.apply(input);
+    }
+
+    // lets go of everything, when the window loses focus or a gamepad is unplugged
+    pub fn release_all(&mut self, input: &mut Input) {
+        self.held.clear();
+        self.axes.clear();
+        self.apply(input);
+    }
+
+    fn is_held(&self, control: &Control) -> bool {
+        match control {
+            Control::GamepadAxis(axis) => self.axis(*axis) > 0.5,
+            _ => self.held.contains(control),
+        }
+    }
+
+    // value with the dead zone cut out, still reaching -1 and 1
+    fn axis(&self, axis: GamepadAxis) -> f64 {
+        let value = self.axes.get(&axis).cloned().unwrap_or(0.0).clamp(-1.0, 1.0);
+        let dead_zone = self.bindings.dead_zone.clamp(0.0, 0.99);
+        if value.abs() <= dead_zone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - dead_zone) / (1.0 - dead_zone)
+        }
+    }
+
+    fn analog(&self, action: AnalogAction) -> f64 {
+        self.bindings
+            .analog
+            .get(&action)
+            .map_or(0.0, |axes| axes.iter().map(|&axis| self.axis(axis)).sum::<f64>())
+            .clamp(-1.0, 1.0)
+    }
+
+    fn apply(&self, input: &mut Input) {
+        let was_paused = input.pause;
+        for (&action, controls) in &self.bindings.actions {
+            let held = controls.iter().any(|control| self.is_held(control));
+            match action {
+                Action::Up => input.up = held,
+                Action::Down => input.down = held,
+                Action::Left => input.left = held,
+                Action::Right => input.right = held,
+                Action::Fire => input.fire = held,
+                Action::Secondary => input.secondary = held,
+                Action::SpawnEnemy => input.spawn_enemy = held,
+                Action::Pause => input.pause = held,
+                Action::ZoomIn => input.zoom_in = held,
+                Action::ZoomOut => input.zoom_out = held,
+            }
+        }
+        if input.pause && !was_paused {
+            input.pause_presses += 1;
+        }
+        input.thrust = [
+            self.analog(AnalogAction::ThrustX),
+            self.analog(AnalogAction::ThrustY),
+        ];
+        input.aim = [
+            self.analog(AnalogAction::AimX),
+            self.analog(AnalogAction::AimY),
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_can_be_left_out() {
+        let bindings: Bindings = ron::de::from_str("(actions: { Fire: [Key(\"F\")] })").unwrap();
+        assert_eq!(bindings.actions[&Action::Fire], vec![Control::Key("F".to_string())]);
+        assert!(bindings.analog.is_empty());
+
+        let bindings: Bindings = ron::de::from_str("(analog: { AimX: [RightX] })").unwrap();
+        assert!(bindings.actions.is_empty());
+        assert_eq!(bindings.analog[&AnalogAction::AimX], vec![GamepadAxis::RightX]);
+    }
+
+    #[test]
+    fn pause_taps_are_counted() {
+        let mut controls = Controls::new(Bindings::builtin());
+        let mut input = Input::default();
+        let p = Control::Key("P".to_string());
+        let start = Control::GamepadButton(GamepadButton::Start);
+
+        // a tap between two updates
+        controls.press(p.clone(), &mut input);
+        controls.release(&p, &mut input);
+        assert!(!input.pause);
+        assert_eq!(input.pause_presses, 1);
+
+        // a second control for the same action doesn't press it again while it's held
+        controls.press(p.clone(), &mut input);
+        controls.press(start.clone(), &mut input);
+        controls.release(&p, &mut input);
+        controls.release(&start, &mut input);
+        assert_eq!(input.pause_presses, 2);
+    }
+
+    fn gamepad() -> Controls {
+        let bindings = r#"(
+            actions: { Fire: [GamepadAxis(RightTrigger)] },
+            analog: { ThrustX: [LeftX, RightX], ThrustY: [LeftY] },
+            dead_zone: 0.2,
+        )"#;
+        Controls::new(ron::de::from_str(bindings).unwrap())
+    }
+
+    #[test]
+    fn sticks_leave_out_the_dead_zone() {
+        let mut controls = gamepad();
+        let mut input = Input::default();
+        let mut thrust_at = |value| {
+            controls.move_axis(GamepadAxis::LeftY, value, &mut input);
+            input.thrust[1]
+        };
+        assert_eq!(thrust_at(0.1), 0.0);
+        assert_eq!(thrust_at(-0.2), 0.0);
+        // the rest of the way is stretched to still reach the ends
+        assert!((thrust_at(0.6) - 0.5).abs() < 1e-9);
+        assert!((thrust_at(-0.6) + 0.5).abs() < 1e-9);
+        assert_eq!(thrust_at(1.0), 1.0);
+        assert_eq!(thrust_at(-1.0), -1.0);
+        assert_eq!(thrust_at(1.5), 1.0);
+    }
+
+    #[test]
+    fn summed_axes_are_clamped() {
+        let mut controls = gamepad();
+        let mut input = Input::default();
+        controls.move_axis(GamepadAxis::LeftX, 1.0, &mut input);
+        controls.move_axis(GamepadAxis::RightX, 1.0, &mut input);
+        assert_eq!(input.thrust, [1.0, 0.0]);
+        controls.move_axis(GamepadAxis::RightX, -0.6, &mut input);
+        assert!((input.thrust[0] - 0.5).abs() < 1e-9);
+        controls.move_axis(GamepadAxis::LeftX, -1.0, &mut input);
+        assert_eq!(input.thrust, [-1.0, 0.0]);
+
+        controls.release_all(&mut input);
+        assert_eq!(input.thrust, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn axes_press_actions_past_halfway() {
+        let mut controls = gamepad();
+        let mut input = Input::default();
+        let mut fire_at = |value| {
+            controls.move_axis(GamepadAxis::RightTrigger, value, &mut input);
+            input.fire
+        };
+        // halfway past the dead zone, not of the whole way
+        assert!(!fire_at(0.5));
+        assert!(!fire_at(0.6));
+        assert!(fire_at(0.7));
+        assert!(fire_at(1.0));
+        assert!(!fire_at(0.0));
+    }
+}
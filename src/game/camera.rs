@@ -0,0 +1,301 @@
+use consts;
+use game::components::{Point, Vector};
+
+// how the camera moves, defaults are tuned for the default zoom
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    // roughly the time in s the camera takes to catch up with its target
+    pub follow_time: f64,
+
+    // the camera looks ahead of its target by the target's velocity times this many s
+    pub velocity_look_ahead: f64,
+    // and by this part of the way to the cursor
+    pub cursor_look_ahead: f64,
+    // but never further than this, in world coords
+    pub max_look_ahead: f64,
+
+    pub min_zoom: f64,
+    pub max_zoom: f64,
+    // how fast the zoom eases towards the requested one, higher is faster
+    pub zoom_rate: f64,
+
+    // trauma lost per s, see `Camera::add_trauma`
+    pub trauma_decay: f64,
+    // how far the screen shakes at full trauma, in pixels
+    pub max_shake: f64,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            follow_time: 0.15,
+            velocity_look_ahead: 0.3,
+            cursor_look_ahead: 0.25,
+            max_look_ahead: 8.0,
+            min_zoom: 5.0,
+            max_zoom: 80.0,
+            zoom_rate: 10.0,
+            trauma_decay: 1.5,
+            max_shake: 20.0,
+        }
+    }
+}
+
+// follows a target with a critically damped spring, eases between zoom levels and shakes
+// only `update` moves it, so it can be driven without a world
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub config: CameraConfig,
+
+    center: Point,
+    velocity: Vector,
+    // where the camera is heading, look-ahead included
+    target: Point,
+
+    zoom: f64,
+    target_zoom: f64,
+
+    // 0 = still, 1 = shaking as much as `CameraConfig::max_shake`
+    trauma: f64,
+    // time the shake has been running, picks the shake offset
+    shake_time: f64,
+
+    // size of the window the camera looks through, see `Input::viewport_size`
+    viewport: Vector,
+
+    // center at the previous tick
+    #[serde(skip)]
+    previous_center: Option<Point>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            config: CameraConfig::default(),
+            center: Point::default(),
+            velocity: Vector::default(),
+            target: Point::default(),
+            zoom: consts::DEFAULT_ZOOM,
+            target_zoom: consts::DEFAULT_ZOOM,
+            trauma: 0.0,
+            shake_time: 0.0,
+            viewport: Vector::new(consts::WINDOW_SIZE[0] as f64, consts::WINDOW_SIZE[1] as f64),
+            previous_center: None,
+        }
+    }
+}
+
+impl Camera {
+    pub fn get_zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    pub fn center(&self) -> Point {
+        self.center
+    }
+
+    // center between the previous tick and the latest one, see `Interpolation`
+    // shaking isn't included, see `shake_offset`
+    pub fn interpolated_center(&self, alpha: f64) -> Point {
+        match self.previous_center {
+            Some(previous) => previous + (self.center - previous) * alpha,
+            None => self.center,
+        }
+    }
+
+    // jumps to `p` without moving there
+    pub fn center_at(&mut self, p: Point) {
+        self.center = p;
+        self.target = p;
+        self.velocity = Vector::default();
+    }
+
+    // remembers the current center for `interpolated_center`
+    pub fn snapshot(&mut self) {
+        self.previous_center = Some(self.center)
+    }
+
+    // heads for `target` moving with `velocity`, `aim` points from the center to the cursor
+    pub fn follow(&mut self, target: Point, velocity: Vector, aim: Vector) {
+        let look_ahead =
+            velocity * self.config.velocity_look_ahead + aim * self.config.cursor_look_ahead;
+        let max = self.config.max_look_ahead;
+        let look_ahead = if look_ahead.length() > max {
+            look_ahead.normalized() * max
+        } else {
+            look_ahead
+        };
+        self.target = target + look_ahead;
+    }
+
+    // the zoom eases towards the new one, within the configured limits
+    pub fn adjust_zoom(&mut self, m: f64) {
+        self.set_zoom(self.target_zoom * m)
+    }
+
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.target_zoom = zoom.clamp(self.config.min_zoom, self.config.max_zoom)
+    }
+
+    // shakes the screen, `amount` from 0 to 1 adds up to at most 1
+    pub fn add_trauma(&mut self, amount: f64) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0)
+    }
+
+    pub fn trauma(&self) -> f64 {
+        self.trauma
+    }
+
+    // moves the camera `dt` s closer to its target and zoom, and calms the shaking
+    pub fn update(&mut self, dt: f64) {
+        // critically damped spring, approximating exp(-omega * dt) by its taylor series
+        // so it doesn't overshoot however long `dt` is
+        let omega = 2.0 / self.config.follow_time.max(1e-6);
+        let x = omega * dt;
+        let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+        let offset = self.center - self.target;
+        let change = (self.velocity + offset * omega) * dt;
+        self.velocity = (self.velocity - change * omega) * decay;
+        self.center = self.target + (offset + change) * decay;
+
+        // ease by ratio, so zooming in and out feel the same
+        let eased = 1.0 - (-self.config.zoom_rate * dt).exp();
+        self.zoom *= (self.target_zoom / self.zoom).powf(eased);
+
+        self.trauma = (self.trauma - self.config.trauma_decay * dt).max(0.0);
+        self.shake_time += dt;
+    }
+
+    // where the screen is shaken to from the center, in world coords
+    // grows with the square of the trauma, so small hits barely shake
+    pub fn shake_offset(&self) -> Vector {
+        // a few sine waves that don't line up look random enough, and are the same every run
+        let t = self.shake_time;
+        let wave = |a: f64, b: f64, phase: f64| {
+            (t * a + phase).sin() * 0.6 + (t * b + phase * 2.0).sin() * 0.4
+        };
+        let strength = self.trauma * self.trauma * self.config.max_shake / self.zoom;
+        Vector::new(wave(47.0, 71.0, 0.0), wave(53.0, 67.0, 1.7)) * strength
+    }
+
+    pub fn viewport(&self) -> Vector {
+        self.viewport
+    }
+
+    pub fn set_viewport(&mut self, size: Vector) {
+        self.viewport = size
+    }
+
+    // visible area in world coords
+    pub fn view_size(&self) -> Vector {
+        self.viewport * (1.0 / self.zoom)
+    }
+
+    // screen coords have (0, 0) in the top left corner of the viewport
+    // the shaking isn't included, so aiming stays steady
+    pub fn world_to_screen(&self, p: Point) -> Point {
+        let offset = (p - self.center) * self.zoom + self.viewport * 0.5;
+        Point::new(offset.dx, offset.dy)
+    }
+
+    pub fn screen_to_world(&self, p: Point) -> Point {
+        let offset = Vector::new(p.x, p.y) - self.viewport * 0.5;
+        self.center + offset * (1.0 / self.zoom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 1.0 / 60.0;
+
+    #[test]
+    fn follows_without_overshooting() {
+        for &dt in &[DT, 0.1, 1.0] {
+            let mut camera = Camera::default();
+            camera.follow(Point::new(10.0, 0.0), Vector::default(), Vector::default());
+            let mut previous = camera.center().x;
+            for _ in 0..(5.0 / dt) as usize {
+                camera.update(dt);
+                let x = camera.center().x;
+                assert!(x >= previous && x <= 10.0, "{} after {} with dt {}", x, previous, dt);
+                previous = x;
+            }
+            assert!((camera.center().x - 10.0).abs() < 1e-6);
+            assert_eq!(camera.center().y, 0.0);
+        }
+    }
+
+    #[test]
+    fn look_ahead_is_limited() {
+        let mut camera = Camera::default();
+        camera.follow(Point::new(1.0, 2.0), Vector::new(1000.0, 0.0), Vector::default());
+        for _ in 0..600 {
+            camera.update(DT);
+        }
+        let max = camera.config.max_look_ahead;
+        assert!((camera.center().x - (1.0 + max)).abs() < 1e-6);
+        assert!((camera.center().y - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn zoom_eases_within_limits() {
+        let mut camera = Camera::default();
+        let (min, max) = (camera.config.min_zoom, camera.config.max_zoom);
+
+        camera.set_zoom(1000.0);
+        let mut previous = camera.get_zoom();
+        for _ in 0..300 {
+            camera.update(DT);
+            assert!(camera.get_zoom() >= previous && camera.get_zoom() <= max);
+            previous = camera.get_zoom();
+        }
+        assert!((camera.get_zoom() - max).abs() < 1e-6);
+
+        camera.adjust_zoom(0.001);
+        for _ in 0..300 {
+            camera.update(DT);
+            assert!(camera.get_zoom() <= previous && camera.get_zoom() >= min);
+            previous = camera.get_zoom();
+        }
+        assert!((camera.get_zoom() - min).abs() < 1e-6);
+    }
+
+    #[test]
+    fn trauma_wears_off() {
+        let mut camera = Camera::default();
+        camera.add_trauma(0.7);
+        camera.add_trauma(0.7);
+        assert_eq!(camera.trauma(), 1.0);
+        assert!(camera.shake_offset().length() <= camera.config.max_shake / camera.get_zoom());
+
+        let mut previous = camera.trauma();
+        for _ in 0..60 {
+            camera.update(DT);
+            assert!(camera.trauma() < previous || camera.trauma() == 0.0);
+            previous = camera.trauma();
+        }
+        assert_eq!(camera.trauma(), 0.0);
+        assert_eq!(camera.shake_offset().length(), 0.0);
+    }
+
+    #[test]
+    fn screen_and_world_coords_round_trip() {
+        let mut camera = Camera::default();
+        camera.center_at(Point::new(12.5, -3.0));
+        camera.set_viewport(Vector::new(640.0, 480.0));
+        camera.set_zoom(33.0);
+        camera.update(1.0);
+        camera.add_trauma(1.0);
+
+        let screen = camera.world_to_screen(Point::new(12.5, -3.0));
+        assert!((screen.x - 320.0).abs() < 1e-9 && (screen.y - 240.0).abs() < 1e-9);
+        for &(x, y) in &[(0.0, 0.0), (12.5, -3.0), (-40.0, 17.25), (1e3, -1e3)] {
+            let p = camera.screen_to_world(camera.world_to_screen(Point::new(x, y)));
+            assert!((p.x - x).abs() < 1e-9 && (p.y - y).abs() < 1e-9, "{:?}", p);
+        }
+    }
+}
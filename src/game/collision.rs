@@ -0,0 +1,446 @@
+use game::components::*;
+
+// smallest distance treated as non-zero when normalising vectors
+const EPSILON: f64 = 1e-9;
+
+// a part of a `Shape` transformed into world coords
+#[derive(Clone, Debug)]
+pub enum Primitive {
+    Circle(Point, f64),
+    // convex polygon, vertices are listed along its outline
+    Convex(Vec<Point>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    // unit vector pointing from the first primitive towards the second
+    pub normal: Vector,
+
+    // overlap along `normal`
+    pub depth: f64,
+
+    // where the primitives touch, roughly in the middle of the overlap
+    pub point: Point,
+}
+
+impl Contact {
+    fn flipped(self) -> Self {
+        Contact {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+// flattens `shape` (recursing into compounds) into world coords primitives
+pub fn world_primitives(shape: &Shape, position: Point, rotation: f64) -> Vec<Primitive> {
+    let mut primitives = Vec::new();
+    collect_primitives(shape, position, rotation, &mut primitives);
+    primitives
+}
+
+fn collect_primitives(shape: &Shape, origin: Point, rotation: f64, output: &mut Vec<Primitive>) {
+    use self::Shape::*;
+
+    match shape {
+        Circle(radius) => output.push(Primitive::Circle(origin, *radius)),
+        Rectangle(size) | Sprite(_, size) => output.push(rectangle(origin, rotation, *size)),
+        Polygon(ref polygon) => {
+            // concave polygons are split into convex triangles
+            let points: Vec<_> = polygon
+                .points()
+                .iter()
+                .map(|p| origin + Vector::new(p.x, p.y).rotated(rotation))
+                .collect();
+            for triangle in polygon.triangles() {
+                output.push(Primitive::Convex(
+                    triangle.iter().map(|&i| points[i]).collect(),
+                ))
+            }
+        }
+        Polyline(ref points, width) => {
+            // a thin rectangle around every segment
+            let points: Vec<_> = points
+                .iter()
+                .map(|p| origin + Vector::new(p.x, p.y).rotated(rotation))
+                .collect();
+            for (&a, &b) in points.iter().zip(points.iter().skip(1)) {
+                output.push(segment(a, b, *width))
+            }
+        }
+        Compound(ref subshapes) => for SubShape {
+            offset,
+            rotation: sub_rotation,
+            shape,
+            ..
+        } in subshapes.iter()
+        {
+            collect_primitives(
+                shape,
+                origin + offset.rotated(rotation),
+                rotation + sub_rotation,
+                output,
+            )
+        },
+    }
+}
+
+fn rectangle(center: Point, rotation: f64, size: Vector) -> Primitive {
+    let (w, h) = (size.dx / 2.0, size.dy / 2.0);
+    Primitive::Convex(
+        [(-w, -h), (w, -h), (w, h), (-w, h)]
+            .iter()
+            .map(|&(dx, dy)| center + Vector::new(dx, dy).rotated(rotation))
+            .collect(),
+    )
+}
+
+fn segment(a: Point, b: Point, width: f64) -> Primitive {
+    let side = (b - a).perpendicular();
+    let side = side * (width / 2.0 / side.length().max(EPSILON));
+    Primitive::Convex(vec![a + side, b + side, b + -side, a + -side])
+}
+
+// the deepest contact between any primitive of `a` and any primitive of `b`
+pub fn collide(a: &[Primitive], b: &[Primitive]) -> Option<Contact> {
+    let mut deepest: Option<Contact> = None;
+    for pa in a {
+        for pb in b {
+            match (intersect(pa, pb), deepest) {
+                (Some(contact), Some(d)) if contact.depth <= d.depth => {}
+                (Some(contact), _) => deepest = Some(contact),
+                (None, _) => {}
+            }
+        }
+    }
+    deepest
+}
+
+pub fn intersect(a: &Primitive, b: &Primitive) -> Option<Contact> {
+    use self::Primitive::*;
+
+    match (a, b) {
+        (Circle(ca, ra), Circle(cb, rb)) => circle_circle(*ca, *ra, *cb, *rb),
+        (Circle(c, r), Convex(poly)) => circle_convex(*c, *r, poly),
+        (Convex(poly), Circle(c, r)) => circle_convex(*c, *r, poly).map(Contact::flipped),
+        (Convex(pa), Convex(pb)) => convex_convex(pa, pb),
+    }
+}
+
+fn circle_circle(ca: Point, ra: f64, cb: Point, rb: f64) -> Option<Contact> {
+    let d = cb - ca;
+    let distance = d.length();
+    if distance >= ra + rb {
+        return None;
+    }
+    let normal = if distance > EPSILON {
+        d * (1.0 / distance)
+    } else {
+        // concentric circles, any direction will do
+        Vector::new(1.0, 0.0)
+    };
+    let depth = ra + rb - distance;
+    Some(Contact {
+        normal,
+        depth,
+        point: ca + normal * (ra - depth / 2.0),
+    })
+}
+
+// normal points from the circle towards the polygon
+fn circle_convex(center: Point, radius: f64, poly: &[Point]) -> Option<Contact> {
+    // find the closest point on the outline
+    let mut closest = poly[0];
+    let mut closest_edge = (poly[0], poly[0]);
+    let mut closest_distance = f64::INFINITY;
+    for (&p1, &p2) in edges(poly) {
+        let q = closest_point_on_segment(center, p1, p2);
+        let distance = (q - center).length();
+        if distance < closest_distance {
+            closest = q;
+            closest_edge = (p1, p2);
+            closest_distance = distance;
+        }
+    }
+
+    let inside = contains(poly, center);
+    if !inside && closest_distance >= radius {
+        return None;
+    }
+
+    let normal = if closest_distance > EPSILON {
+        let to_outline = (closest - center) * (1.0 / closest_distance);
+        // when the centre is inside the polygon has to move the other way
+        if inside {
+            -to_outline
+        } else {
+            to_outline
+        }
+    } else {
+        // centre exactly on the outline, push the polygon away along the edge normal
+        -outward_normal(poly, closest_edge.0, closest_edge.1)
+    };
+    Some(Contact {
+        normal,
+        depth: if inside {
+            radius + closest_distance
+        } else {
+            radius - closest_distance
+        },
+        point: closest,
+    })
+}
+
+// separating axis test, normal points from `a` towards `b`
+fn convex_convex(a: &[Point], b: &[Point]) -> Option<Contact> {
+    // the axis with the least overlap and the overlap
+    let mut best: Option<(Vector, f64)> = None;
+    for (&p1, &p2) in edges(a).chain(edges(b)) {
+        let axis = (p2 - p1).perpendicular();
+        let length = axis.length();
+        if length <= EPSILON {
+            continue;
+        }
+        let axis = axis * (1.0 / length);
+
+        let (min_a, max_a) = project(a, axis);
+        let (min_b, max_b) = project(b, axis);
+        let overlap = max_a.min(max_b) - min_a.max(min_b);
+        if overlap <= 0.0 {
+            // found a separating axis
+            return None;
+        }
+        match best {
+            Some((_, depth)) if depth <= overlap => {}
+            _ => best = Some((axis, overlap)),
+        }
+    }
+
+    best.map(|(normal, depth)| {
+        let normal = if (centroid(b) - centroid(a)).dot(normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        Contact {
+            normal,
+            depth,
+            point: convex_contact_point(a, b, normal),
+        }
+    })
+}
+
+// middle of the corners inside the other polygon, or of the deepest corners
+// when the polygons cross without any corner inside
+fn convex_contact_point(a: &[Point], b: &[Point], normal: Vector) -> Point {
+    let inside: Vec<_> = a
+        .iter()
+        .filter(|&&p| contains(b, p))
+        .chain(b.iter().filter(|&&p| contains(a, p)))
+        .cloned()
+        .collect();
+    if !inside.is_empty() {
+        return centroid(&inside);
+    }
+    let deepest_a = support(a, normal);
+    let deepest_b = support(b, -normal);
+    deepest_a + (deepest_b - deepest_a) * 0.5
+}
+
+fn edges(poly: &[Point]) -> impl Iterator<Item = (&Point, &Point)> {
+    poly.iter().zip(poly.iter().cycle().skip(1))
+}
+
+fn project(poly: &[Point], axis: Vector) -> (f64, f64) {
+    poly.iter()
+        .map(|p| Vector::new(p.x, p.y).dot(axis))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), d| {
+            (min.min(d), max.max(d))
+        })
+}
+
+// the corner furthest along `direction`
+fn support(poly: &[Point], direction: Vector) -> Point {
+    let distance = |p: Point| Vector::new(p.x, p.y).dot(direction);
+    poly.iter()
+        .cloned()
+        .fold(poly[0], |best, p| if distance(p) > distance(best) { p } else { best })
+}
+
+fn centroid(poly: &[Point]) -> Point {
+    let n = poly.len() as f64;
+    let (x, y) = poly
+        .iter()
+        .fold((0.0, 0.0), |(x, y), p| (x + p.x / n, y + p.y / n));
+    Point::new(x, y)
+}
+
+fn closest_point_on_segment(p: Point, a: Point, b: Point) -> Point {
+    let ab = b - a;
+    let length_squared = ab.dot(ab);
+    if length_squared <= EPSILON {
+        return a;
+    }
+    let t = ((p - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+fn outward_normal(poly: &[Point], p1: Point, p2: Point) -> Vector {
+    let normal = (p2 - p1).perpendicular();
+    let normal = normal * (1.0 / normal.length().max(EPSILON));
+    if (p1 - centroid(poly)).dot(normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+// works for both windings, points on the outline count as inside
+fn contains(poly: &[Point], p: Point) -> bool {
+    let mut sign = 0.0;
+    for (&p1, &p2) in edges(poly) {
+        let cross = (p2 - p1).perpendicular().dot(p - p1);
+        if cross != 0.0 {
+            if sign * cross < 0.0 {
+                return false;
+            }
+            sign = cross;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
+
+    fn circle(x: f64, y: f64, radius: f64) -> Vec<Primitive> {
+        world_primitives(&Shape::Circle(radius), Point::new(x, y), 0.0)
+    }
+
+    fn assert_contact(contact: Option<Contact>, normal: Vector, depth: f64) -> Contact {
+        let contact = contact.expect("no contact");
+        assert!((contact.normal - normal).length() < 1e-9, "normal {:?}", contact.normal);
+        assert!((contact.depth - depth).abs() < 1e-9, "depth {}", contact.depth);
+        contact
+    }
+
+    #[test]
+    fn circles() {
+        let contact = collide(&circle(0.0, 0.0, 1.0), &circle(1.5, 0.0, 1.0));
+        let contact = assert_contact(contact, Vector::new(1.0, 0.0), 0.5);
+        assert!((contact.point - Point::new(0.75, 0.0)).length() < 1e-9);
+
+        let contact = collide(&circle(0.0, 1.5, 1.0), &circle(0.0, 0.0, 1.0));
+        assert_contact(contact, Vector::new(0.0, -1.0), 0.5);
+        assert!(collide(&circle(0.0, 0.0, 1.0), &circle(2.5, 0.0, 1.0)).is_none());
+        // on top of each other, still pushed apart somehow
+        assert_contact(
+            collide(&circle(1.0, 1.0, 1.0), &circle(1.0, 1.0, 0.5)),
+            Vector::new(1.0, 0.0),
+            1.5,
+        );
+    }
+
+    #[test]
+    fn turned_rectangle_and_circle() {
+        // a diamond with its edges 1 away from the middle
+        let square = Shape::Rectangle(Vector::new(2.0, 2.0));
+        let diamond = world_primitives(&square, Point::new(0.0, 0.0), FRAC_PI_4);
+        let diagonal = Vector::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2);
+
+        // against an edge
+        let center = Point::new(0.0, 0.0) + diagonal * 1.3;
+        let contact = collide(&diamond, &world_primitives(&Shape::Circle(0.5), center, 0.0));
+        let contact = assert_contact(contact, diagonal, 0.2);
+        assert!((contact.point - (Point::new(0.0, 0.0) + diagonal)).length() < 1e-9);
+        let contact = collide(&world_primitives(&Shape::Circle(0.5), center, 0.0), &diamond);
+        assert_contact(contact, -diagonal, 0.2);
+
+        // against a corner, which an unturned square wouldn't reach
+        let corner = 2.0f64.sqrt();
+        assert_contact(
+            collide(&diamond, &circle(1.6, 0.0, 0.5)),
+            Vector::new(1.0, 0.0),
+            0.5 - (1.6 - corner),
+        );
+        assert!(collide(&diamond, &circle(1.6, 1.6, 0.5)).is_none());
+
+        // with the centre inside
+        let square = world_primitives(&square, Point::new(0.0, 0.0), 0.0);
+        assert_contact(collide(&square, &circle(0.8, 0.0, 0.5)), Vector::new(1.0, 0.0), 0.7);
+    }
+
+    #[test]
+    fn rectangles() {
+        let square = Shape::Rectangle(Vector::new(2.0, 2.0));
+        let a = world_primitives(&square, Point::new(0.0, 0.0), 0.0);
+        let b = world_primitives(&square, Point::new(1.5, 0.2), 0.0);
+        assert_contact(collide(&a, &b), Vector::new(1.0, 0.0), 0.5);
+        assert_contact(collide(&b, &a), Vector::new(-1.0, 0.0), 0.5);
+
+        let c = world_primitives(&square, Point::new(2.5, 0.0), FRAC_PI_4);
+        assert!(collide(&a, &c).is_none());
+        let c = world_primitives(&square, Point::new(2.2, 0.0), FRAC_PI_4);
+        assert_contact(collide(&a, &c), Vector::new(1.0, 0.0), 2.0f64.sqrt() - 1.2);
+    }
+
+    #[test]
+    fn compound_parts_are_placed_and_turned() {
+        let part = |dx| SubShape {
+            offset: Vector::new(dx, 0.0),
+            rotation: 0.0,
+            shape: Shape::Circle(0.5),
+            material: None,
+        };
+        // parts left and right of the middle, turned up and down
+        let compound = Shape::Compound(vec![part(-2.0), part(2.0)]);
+        let compound = world_primitives(&compound, Point::new(1.0, 1.0), FRAC_PI_2);
+        assert_eq!(compound.len(), 2);
+
+        assert_contact(
+            collide(&compound, &circle(1.0, 4.0, 1.0)),
+            Vector::new(0.0, 1.0),
+            0.5,
+        );
+        assert_contact(
+            collide(&compound, &circle(1.0, -1.8, 1.0)),
+            Vector::new(0.0, -1.0),
+            0.7,
+        );
+        // the deepest part wins
+        let big = circle(1.0, 1.5, 2.0);
+        assert_contact(collide(&compound, &big), Vector::new(0.0, -1.0), 1.0);
+        assert!(collide(&compound, &circle(3.0, 1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn concave_polygons() {
+        use game::polygon::Polygon;
+
+        // a U with a notch from (1, 1) to (2, 3), moved right by 10
+        let points = [
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 3.0),
+            (2.0, 3.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+        ];
+        let u = Shape::Polygon(Polygon::new(
+            points.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+        ));
+        let u = world_primitives(&u, Point::new(10.0, 0.0), 0.0);
+
+        // fits into the notch, where the outline's hull would hit it
+        assert!(collide(&u, &circle(11.5, 2.2, 0.4)).is_none());
+        // pushed out of the left arm
+        assert_contact(collide(&u, &circle(11.4, 2.2, 0.6)), Vector::new(1.0, 0.0), 0.2);
+        // and from below the notch
+        assert_contact(collide(&u, &circle(11.5, 1.3, 0.4)), Vector::new(0.0, 1.0), 0.1);
+        assert!(collide(&u, &circle(14.0, 1.0, 0.9)).is_none());
+    }
+}
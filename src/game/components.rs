@@ -1,22 +1,58 @@
 use consts;
+use game::camera::Camera;
+use game::physics;
+use game::polygon::Polygon;
+use game::prefabs::Prefabs;
+use game::render::{layers, Color};
+use game::spatial::SpatialGrid;
+use game::sprites::SpriteAtlas;
+use game::waves::WaveDirector;
+use rand::prng::XorShiftRng;
+use rand::{self, Rng, SeedableRng};
+use shrev::EventChannel;
+use specs::saveload::{U64Marker, U64MarkerAllocator};
 use specs::*;
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Neg, Sub};
 
 pub fn create_world() -> World {
     let mut world = World::new();
     world.add_resource(Input::default());
     world.add_resource(Clock::default());
+    world.add_resource(Interpolation::default());
+    world.add_resource(Random::new(rand::random()));
     world.add_resource(Camera::default());
     world.add_resource(None as Option<Player>);
+    world.add_resource(Collisions::default());
+    world.add_resource(SpatialGrid::default());
+    world.add_resource(EventChannel::<GameEvent>::new());
+    world.add_resource(WaveDirector::default());
+    world.add_resource(Prefabs::builtin());
+    world.add_resource(SpriteAtlas::default());
+    world.add_resource(U64MarkerAllocator::new());
     world.register::<Position>();
     world.register::<Rotation>();
+    world.register::<PreviousTransform>();
     world.register::<Velocity>();
     world.register::<Acceleration>();
     world.register::<AngularVelocity>();
+    world.register::<FlightModel>();
+    world.register::<RigidBody>();
+    world.register::<Mass>();
+    world.register::<Force>();
     world.register::<Shape>();
+    world.register::<Material>();
+    world.register::<Weapon>();
+    world.register::<Projectile>();
+    world.register::<Lifetime>();
+    world.register::<Health>();
+    world.register::<Damage>();
+    world.register::<AiBehaviour>();
+    world.register::<U64Marker>();
     world
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -28,7 +64,23 @@ impl Point {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+impl Add<Vector> for Point {
+    type Output = Point;
+
+    fn add(self, v: Vector) -> Point {
+        Point::new(self.x + v.dx, self.y + v.dy)
+    }
+}
+
+impl Sub for Point {
+    type Output = Vector;
+
+    fn sub(self, p: Point) -> Vector {
+        Vector::new(self.x - p.x, self.y - p.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Vector {
     pub dx: f64,
     pub dy: f64,
@@ -38,38 +90,158 @@ impl Vector {
     pub fn new(dx: f64, dy: f64) -> Self {
         Vector { dx, dy }
     }
+
+    pub fn dot(self, v: Vector) -> f64 {
+        self.dx * v.dx + self.dy * v.dy
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    // rotated by `angle` radians, the same way `Rotation` rotates shapes
+    pub fn rotated(self, angle: f64) -> Vector {
+        let (sin, cos) = angle.sin_cos();
+        Vector::new(self.dx * cos - self.dy * sin, self.dx * sin + self.dy * cos)
+    }
+
+    // angle in radians, as used by `Rotation`
+    pub fn angle(self) -> f64 {
+        self.dy.atan2(self.dx)
+    }
+
+    // same direction with length 1, zero vector stays zero
+    pub fn normalized(self) -> Vector {
+        let length = self.length();
+        if length > 0.0 {
+            self * (1.0 / length)
+        } else {
+            self
+        }
+    }
+
+    // perpendicular vector, rotated by 90 degrees
+    pub fn perpendicular(self) -> Vector {
+        Vector::new(-self.dy, self.dx)
+    }
 }
 
-#[derive(Default, Debug)]
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, v: Vector) -> Vector {
+        Vector::new(self.dx + v.dx, self.dy + v.dy)
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, v: Vector) -> Vector {
+        Vector::new(self.dx - v.dx, self.dy - v.dy)
+    }
+}
+
+impl Mul<f64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, m: f64) -> Vector {
+        Vector::new(self.dx * m, self.dy * m)
+    }
+}
+
+impl Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector::new(-self.dx, -self.dy)
+    }
+}
+
+// what the player does, by action, see `bindings::Controls` for how controls map to it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Input {
+    // held thrust directions
     pub left: bool,
     pub right: bool,
     pub up: bool,
     pub down: bool,
+    // analog thrust from -1 to 1 on both axes, from sticks
+    pub thrust: [f64; 2],
+
+    #[serde(alias = "mouse_left")]
+    pub fire: bool,
+    // spawns dummy particles for now
+    #[serde(alias = "mouse_right")]
+    pub secondary: bool,
+    #[serde(alias = "mouse_middle")]
+    pub spawn_enemy: bool,
+    pub pause: bool,
+    // times pause was pressed since the last `Game::update`, so taps between updates count too
+    pub pause_presses: u32,
+    pub zoom_in: bool,
+    pub zoom_out: bool,
 
-    pub mouse_left: bool,
     pub mouse_scroll: [f64; 2],
     pub mouse_position: [f64; 2],
+    // analog aim from -1 to 1 on both axes, aims along it instead of at the mouse when not zero
+    pub aim: [f64; 2],
+
+    // size of the window, in the same units as `mouse_position`
+    pub viewport_size: [f64; 2],
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input {
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            thrust: [0.0, 0.0],
+            fire: false,
+            secondary: false,
+            spawn_enemy: false,
+            pause: false,
+            pause_presses: 0,
+            zoom_in: false,
+            zoom_out: false,
+            mouse_scroll: [0.0, 0.0],
+            mouse_position: [0.0, 0.0],
+            aim: [0.0, 0.0],
+            viewport_size: [consts::WINDOW_SIZE[0] as f64, consts::WINDOW_SIZE[1] as f64],
+        }
+    }
 }
 
 impl Input {
-    pub fn keyboard_direction(&self) -> Vector {
-        use std::f64::consts::SQRT_2;
-        if (self.left || self.right) && (self.up || self.down) {
-            Vector::new(
-                if self.left { -SQRT_2 } else { SQRT_2 },
-                if self.up { -SQRT_2 } else { SQRT_2 },
-            )
-        } else if self.left || self.right {
-            Vector::new(if self.left { -1.0 } else { 1.0 }, 0.0)
-        } else if self.up || self.down {
-            Vector::new(0.0, if self.up { -1.0 } else { 1.0 })
+    // where to thrust, held directions and sticks added up, at most 1 long
+    pub fn direction(&self) -> Vector {
+        let held = |positive: bool, negative: bool| positive as i32 as f64 - negative as i32 as f64;
+        let direction = Vector::new(
+            held(self.right, self.left) + self.thrust[0],
+            held(self.down, self.up) + self.thrust[1],
+        );
+        if direction.length() > 1.0 {
+            direction.normalized()
         } else {
-            Vector::new(0.0, 0.0)
+            direction
+        }
+    }
+
+    // direction of the aim stick, `None` when it's let go
+    pub fn aim_direction(&self) -> Option<Vector> {
+        let [dx, dy] = self.aim;
+        if dx == 0.0 && dy == 0.0 {
+            None
+        } else {
+            Some(Vector::new(dx, dy))
         }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Clock {
     // last update delta
     pub delta: f64,
@@ -77,7 +249,7 @@ pub struct Clock {
     // current world time
     pub time: f64,
 
-    // 0 = paused, 1 = real-time
+    // 0 = paused, 1 = real-time, scales how many ticks `Game::update` runs
     pub simulation_speed: f64,
 }
 
@@ -93,46 +265,116 @@ impl Default for Clock {
 
 impl Clock {
     pub fn advance(&mut self, delta: f64) {
-        self.delta = delta * self.simulation_speed;
+        self.delta = delta;
         self.time += self.delta;
     }
 }
 
-pub struct Camera {
-    center: Point,
-    zoom: f64,
+// random number generator of the game logic
+// everything random in the simulation draws from it, so the same seed gives the same game
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Random {
+    seed: u64,
+    rng: XorShiftRng,
 }
 
-impl Default for Camera {
+impl Default for Random {
     fn default() -> Self {
-        Camera {
-            center: Point::default(),
-            zoom: consts::DEFAULT_ZOOM,
-        }
+        Random::new(0)
     }
 }
 
-impl Camera {
-    pub fn get_center_point(&self) -> Point {
-        self.center
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // spread the u64 over the 16 seed bytes
+        let mut seed_bytes = [0u8; 16];
+        for (i, byte) in seed_bytes.iter_mut().enumerate() {
+            *byte = (seed >> (8 * (i % 8))) as u8;
+        }
+        Random {
+            seed,
+            rng: XorShiftRng::from_seed(seed_bytes),
+        }
+    }
+
+    // the seed this generator started from
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    pub fn get_zoom(&self) -> f64 {
-        self.zoom
+    // uniformly distributed in [from, to)
+    pub fn range(&mut self, from: f64, to: f64) -> f64 {
+        from + (to - from) * self.rng.gen::<f64>()
     }
 
-    pub fn center_at(&mut self, p: Point) {
-        self.center = p
+    pub fn coin_flip(&mut self) -> bool {
+        self.rng.gen()
     }
+}
+
+// how far rendering is between the previous tick and the latest one
+// 0 = previous tick, 1 = latest tick
+#[derive(Clone, Copy, Debug)]
+pub struct Interpolation {
+    pub alpha: f64,
+}
 
-    pub fn adjust_zoom(&mut self, m: f64) {
-        self.zoom *= m
+impl Default for Interpolation {
+    fn default() -> Self {
+        Interpolation { alpha: 1.0 }
     }
 }
 
 pub struct Player(pub Entity);
 
-#[derive(Component, Debug, Default)]
+// a pair of overlapping entities found during the last update
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Collision {
+    pub a: Entity,
+    pub b: Entity,
+
+    // unit vector pointing from `a` towards `b`
+    pub normal: Vector,
+
+    // how far `b` has to move along `normal` to stop overlapping `a`
+    pub depth: f64,
+
+    // where they touch, in world coords
+    pub point: Point,
+}
+
+// collisions from the last update, rebuilt every update by `CollisionSys`
+#[derive(Default, Debug)]
+pub struct Collisions(pub Vec<Collision>);
+
+// written by systems into `EventChannel<GameEvent>`, collected by `Game::update`
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+    // a new entity will appear at the end of the update
+    Spawned { entity: Entity },
+
+    // index into the loaded waves
+    WaveStarted { wave: usize },
+
+    Fired { shooter: Entity, projectile: Entity },
+
+    // for projectiles `attacker` is the shooter
+    Hit {
+        target: Entity,
+        attacker: Entity,
+        damage: f64,
+    },
+
+    // health dropped to zero, the entity is deleted at the end of the update
+    Destroyed {
+        entity: Entity,
+        killer: Option<Entity>,
+    },
+}
+
+#[derive(Clone, Component, Debug, Default, Serialize, Deserialize)]
 #[storage(VecStorage)]
 // coords in world system
 pub struct Position(pub Point);
@@ -143,7 +385,7 @@ impl Position {
     }
 }
 
-#[derive(Component, Debug, Default)]
+#[derive(Clone, Component, Debug, Default, Serialize, Deserialize)]
 #[storage(VecStorage)]
 // rotation in radians
 pub struct Rotation(pub f64);
@@ -154,7 +396,32 @@ impl Rotation {
     }
 }
 
-#[derive(Component, Debug, Default)]
+#[derive(Clone, Component, Debug)]
+#[storage(VecStorage)]
+// position and rotation at the previous tick, for drawing in between ticks
+pub struct PreviousTransform {
+    pub position: Point,
+    pub rotation: f64,
+}
+
+impl PreviousTransform {
+    // transform between this one and the current one, see `Interpolation`
+    pub fn interpolate(
+        &self,
+        position: &Position,
+        rotation: &Rotation,
+        alpha: f64,
+    ) -> (Point, f64) {
+        // turn the short way round, rotations aren't normalized
+        let turn = (rotation.0 - self.rotation + PI).rem_euclid(2.0 * PI) - PI;
+        (
+            self.position + (position.0 - self.position) * alpha,
+            self.rotation + turn * alpha,
+        )
+    }
+}
+
+#[derive(Clone, Component, Debug, Default, Serialize, Deserialize)]
 #[storage(VecStorage)]
 // ∆ world system coords / s
 pub struct Velocity(pub Vector);
@@ -165,7 +432,7 @@ impl Velocity {
     }
 }
 
-#[derive(Component, Debug, Default)]
+#[derive(Clone, Component, Debug, Default, Serialize, Deserialize)]
 #[storage(VecStorage)]
 // ∆ world system coords / s / s
 pub struct Acceleration(pub Vector);
@@ -176,7 +443,7 @@ impl Acceleration {
     }
 }
 
-#[derive(Component, Debug, Default)]
+#[derive(Clone, Component, Debug, Default, Serialize, Deserialize)]
 #[storage(VecStorage)]
 // ∆ rotation in radians / s
 pub struct AngularVelocity(pub f64);
@@ -187,18 +454,462 @@ impl AngularVelocity {
     }
 }
 
+// how thrust is steered, see `FlightModel`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FlightMode {
+    // up on the screen is always up
+    Arcade,
+    // up thrusts along `Rotation`, left and right strafe sideways to it
+    Newtonian,
+}
+
+// turns thrust into movement, entities without one keep every bit of speed they get
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+#[serde(default)]
+pub struct FlightModel {
+    pub mode: FlightMode,
+
+    // part of the velocity lost every s, as in dv / dt = -drag * v
+    pub linear_drag: f64,
+    // the same for `AngularVelocity`
+    pub angular_drag: f64,
+
+    // world system coords / s, `None` = no limit
+    pub max_speed: Option<f64>,
+
+    // world system coords / s / s, full thrust
+    pub max_acceleration: f64,
+}
+
+impl Default for FlightModel {
+    // flies like entities without one, with the thrust the player used to have
+    fn default() -> Self {
+        FlightModel {
+            mode: FlightMode::Arcade,
+            linear_drag: 0.0,
+            angular_drag: 0.0,
+            max_speed: None,
+            max_acceleration: 20.0,
+        }
+    }
+}
+
+impl FlightModel {
+    // acceleration for `direction`, at most 1 long, where "up" is negative `dy`
+    pub fn thrust(&self, direction: Vector, rotation: f64) -> Vector {
+        let thrust = match self.mode {
+            FlightMode::Arcade => direction,
+            FlightMode::Newtonian => {
+                let heading = Vector::new(1.0, 0.0).rotated(rotation);
+                heading * -direction.dy + heading.perpendicular() * direction.dx
+            }
+        };
+        thrust * self.max_acceleration
+    }
+
+    // velocity `dt` s later, accelerating by `acceleration` (limited to `max_acceleration`)
+    // exact for constant acceleration, so it doesn't depend on the tick rate
+    pub fn integrate(&self, velocity: Vector, acceleration: Vector, dt: f64) -> Vector {
+        let acceleration = if acceleration.length() > self.max_acceleration {
+            acceleration.normalized() * self.max_acceleration
+        } else {
+            acceleration
+        };
+        let velocity = if self.linear_drag > 0.0 {
+            // solution of dv / dt = a - drag * v, heading for a / drag
+            let decay = (-self.linear_drag * dt).exp();
+            velocity * decay + acceleration * ((1.0 - decay) / self.linear_drag)
+        } else {
+            velocity + acceleration * dt
+        };
+        match self.max_speed {
+            Some(max_speed) if velocity.length() > max_speed => velocity.normalized() * max_speed,
+            _ => velocity,
+        }
+    }
+
+    // angular velocity `dt` s later, slowed down by `angular_drag`
+    pub fn integrate_angular(&self, angular_velocity: f64, dt: f64) -> f64 {
+        angular_velocity * (-self.angular_drag * dt).exp()
+    }
+}
+
+// makes an entity bounce off other rigid bodies, see `physics`
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+#[serde(default)]
+pub struct RigidBody {
+    // mass per area of the shape
+    pub density: f64,
+
+    // 0 = bumps stop dead, 1 = bumps bounce back at full speed
+    pub restitution: f64,
+
+    // how much sliding along each other slows both bodies down, usually from 0 to 1
+    pub friction: f64,
+
+    // bumps never spin it, for entities that turn by themselves
+    pub fixed_rotation: bool,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        RigidBody {
+            density: 1.0,
+            restitution: 0.3,
+            friction: 0.3,
+            fixed_rotation: false,
+        }
+    }
+}
+
+// worked out from `Shape` and `RigidBody` by `MassSys`, remove it to work it out again
 #[derive(Clone, Component, Debug)]
 #[storage(VecStorage)]
+pub struct Mass {
+    pub mass: f64,
+
+    // center of mass relative to the entity's position, before turning it by `Rotation`
+    pub center: Vector,
+
+    // moment of inertia around the center, infinite for `RigidBody::fixed_rotation`
+    pub inertia: f64,
+}
+
+impl Mass {
+    pub fn new(shape: &Shape, body: &RigidBody) -> Self {
+        let (mass, center, inertia) = physics::mass_properties(shape, body.density);
+        Mass {
+            mass,
+            center,
+            inertia: if body.fixed_rotation {
+                f64::INFINITY
+            } else {
+                inertia
+            },
+        }
+    }
+}
+
+// pushes on a rigid body during the next tick, see `Game::apply_force`
+#[derive(Clone, Component, Debug, Default)]
+#[storage(VecStorage)]
+pub struct Force {
+    // mass * world system coords / s / s
+    pub force: Vector,
+
+    // mass * world system coords ^ 2 * radians / s / s, around the entity's position
+    pub torque: f64,
+}
+
+impl Force {
+    // adds `force` pushing at the world point `point` of a body at `center`
+    pub fn add_at(&mut self, force: Vector, point: Point, center: Point) {
+        self.force = self.force + force;
+        self.torque += (point - center).perpendicular().dot(force);
+    }
+}
+
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
 pub enum Shape {
     Circle(f64),
     Rectangle(Vector),
     Sprite(String, Vector),
+    // outline points around the shape origin, convex or not
+    Polygon(Polygon),
+    // connected line segments and their width
+    Polyline(Vec<Point>, f64),
     Compound(Vec<SubShape>),
 }
 
-#[derive(Clone, Debug)]
+impl Shape {
+    // radius of a circle around the shape origin containing the whole shape
+    pub fn bounding_radius(&self) -> f64 {
+        use self::Shape::*;
+
+        match self {
+            Circle(radius) => *radius,
+            Rectangle(size) | Sprite(_, size) => (*size * 0.5).length(),
+            Polygon(ref polygon) => farthest_point(polygon.points()),
+            Polyline(ref points, width) => farthest_point(points) + width / 2.0,
+            Compound(ref subshapes) => subshapes
+                .iter()
+                .map(|s| s.offset.length() + s.shape.bounding_radius())
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+fn farthest_point(points: &[Point]) -> f64 {
+    points
+        .iter()
+        .map(|p| Vector::new(p.x, p.y).length())
+        .fold(0.0, f64::max)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SubShape {
     pub offset: Vector,
     pub rotation: f64,
     pub shape: Shape,
+
+    // replaces the entity's material for this part of the shape
+    #[serde(default)]
+    pub material: Option<Material>,
+}
+
+// how an entity's shape is drawn, entities without one are drawn with the default
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+#[serde(default)]
+pub struct Material {
+    // colour inside the shape, `None` uses the colour of the shape kind
+    // sprites are tinted with it
+    pub fill: Option<Color>,
+
+    // line centered on the edge of the shape, `outline_width` wide in world coords
+    pub outline: Option<Color>,
+    pub outline_width: f64,
+
+    // opacity of both colours, from 0 to 1
+    pub alpha: f32,
+
+    // draw order, see `render::layers`
+    pub layer: i32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            fill: None,
+            outline: None,
+            outline_width: 0.1,
+            alpha: 1.0,
+            layer: layers::ENTITIES,
+        }
+    }
+}
+
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Weapon {
+    // shots / s
+    pub fire_rate: f64,
+
+    // world system coords / s, relative to the shooter
+    pub projectile_speed: f64,
+
+    // s
+    pub projectile_lifetime: f64,
+
+    // max deviation from the aim in radians, both ways
+    pub spread: f64,
+
+    pub damage: f64,
+
+    // where projectiles appear, in shooter's coords
+    pub muzzle: Vector,
+
+    // set by whoever controls the shooter
+    #[serde(default)]
+    pub firing: bool,
+
+    // world time at which the next shot can be fired
+    #[serde(default)]
+    pub ready_at: f64,
+}
+
+impl Weapon {
+    // delay between shots in s
+    pub fn cooldown(&self) -> f64 {
+        1.0 / self.fire_rate
+    }
+}
+
+#[derive(Clone, Component, Debug)]
+#[storage(VecStorage)]
+pub struct Projectile {
+    // the shooter, projectiles don't hit it and it gets credit for kills
+    // can be `None` for projectiles restored from a save after their shooter was gone
+    pub owner: Option<Entity>,
+}
+
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+// s left before the entity is deleted
+pub struct Lifetime(pub f64);
+
+#[derive(Clone, Component, Debug)]
+#[storage(VecStorage)]
+pub struct Health {
+    pub hit_points: f64,
+
+    // absorbs damage before hit points do
+    pub shield: f64,
+
+    // whoever dealt the last hit
+    pub last_attacker: Option<Entity>,
+}
+
+impl Health {
+    pub fn new(hit_points: f64, shield: f64) -> Self {
+        Health {
+            hit_points,
+            shield,
+            last_attacker: None,
+        }
+    }
+
+    pub fn take_damage(&mut self, amount: f64, attacker: Entity) {
+        let absorbed = amount.min(self.shield);
+        self.shield -= absorbed;
+        self.hit_points = (self.hit_points - (amount - absorbed)).max(0.0);
+        self.last_attacker = Some(attacker);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hit_points <= 0.0
+    }
+}
+
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+// dealt to every entity with `Health` this one touches, once per update
+pub struct Damage(pub f64);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Steering {
+    // head straight for the player
+    Seek,
+
+    // run away from the player
+    Flee,
+
+    // circle around the player at `distance`, facing where it's going
+    Orbit { distance: f64 },
+
+    // circle around the player at `distance`, facing and shooting at it
+    Strafe { distance: f64 },
+
+    // drift around aimlessly
+    Wander,
+}
+
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct AiBehaviour {
+    pub steering: Steering,
+
+    // the player is ignored (and the entity wanders) when it's further than this
+    pub sight_range: f64,
+
+    // world system coords / s
+    pub max_speed: f64,
+
+    // world system coords / s / s
+    pub max_acceleration: f64,
+
+    // current heading used by `Steering::Wander`
+    #[serde(default)]
+    pub wander_angle: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 1.0 / 60.0;
+
+    fn close(a: Vector, b: Vector) -> bool {
+        (a.dx - b.dx).abs() < 1e-9 && (a.dy - b.dy).abs() < 1e-9
+    }
+
+    fn drifting() -> FlightModel {
+        FlightModel { linear_drag: 0.5, angular_drag: 2.0, ..FlightModel::default() }
+    }
+
+    #[test]
+    fn drag_matches_the_exact_solution() {
+        let model = drifting();
+        let (v, a, k) = (Vector::new(3.0, -4.0), Vector::new(6.0, 8.0), model.linear_drag);
+        for &t in &[DT, 0.5, 2.0] {
+            let decay = (-k * t).exp();
+            let expected = v * decay + a * ((1.0 - decay) / k);
+            assert!(close(model.integrate(v, a, t), expected), "after {} s", t);
+        }
+        let expected = 5.0 * (-model.angular_drag).exp();
+        assert!((model.integrate_angular(5.0, 1.0) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tick_rate_does_not_matter() {
+        let model = drifting();
+        let (v, a) = (Vector::new(1.0, 2.0), Vector::new(-10.0, 5.0));
+        let mut ticked = v;
+        let mut spun = 3.0;
+        for _ in 0..60 {
+            ticked = model.integrate(ticked, a, DT);
+            spun = model.integrate_angular(spun, DT);
+        }
+        assert!(close(ticked, model.integrate(v, a, 1.0)));
+        assert!((spun - model.integrate_angular(3.0, 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_drag_is_plain_euler() {
+        let model = FlightModel::default();
+        let (v, a) = (Vector::new(1.0, 2.0), Vector::new(3.0, -4.0));
+        assert!(close(model.integrate(v, a, 0.5), Vector::new(2.5, 0.0)));
+        assert_eq!(model.integrate_angular(3.0, 0.5), 3.0);
+    }
+
+    #[test]
+    fn acceleration_and_speed_are_limited() {
+        let model = FlightModel { max_speed: Some(50.0), ..FlightModel::default() };
+        let max = model.max_acceleration;
+        let v = model.integrate(Vector::default(), Vector::new(0.0, 10.0 * max), 1.0);
+        assert!(close(v, Vector::new(0.0, max)));
+
+        let mut v = Vector::new(40.0, 0.0);
+        for _ in 0..600 {
+            v = model.integrate(v, Vector::new(max, max), DT);
+            assert!(v.length() <= 50.0 + 1e-9, "{:?}", v);
+        }
+        assert!((v.length() - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thrust_follows_the_mode() {
+        let mut model = FlightModel::default();
+        let max = model.max_acceleration;
+        let up = Vector::new(0.0, -1.0);
+        let right = Vector::new(1.0, 0.0);
+        let rotation = PI / 2.0;
+        assert!(close(model.thrust(up, rotation), up * max));
+
+        model.mode = FlightMode::Newtonian;
+        let heading = Vector::new(1.0, 0.0).rotated(rotation);
+        assert!(close(model.thrust(up, rotation), heading * max));
+        assert!(close(model.thrust(right, rotation), heading.perpendicular() * max));
+        assert!(close(model.thrust(up, 0.0), right * max));
+    }
+
+    #[test]
+    fn bounding_radius_covers_every_point() {
+        let points = vec![Point::new(1.0, 0.0), Point::new(-3.0, 4.0), Point::new(0.0, -2.0)];
+        let polygon = Shape::Polygon(Polygon::new(points.clone()));
+        assert_eq!(polygon.bounding_radius(), 5.0);
+        // half the width sticks out past the end points
+        assert_eq!(Shape::Polyline(points, 1.0).bounding_radius(), 5.5);
+
+        let compound = Shape::Compound(vec![SubShape {
+            offset: Vector::new(0.0, 2.0),
+            rotation: 1.0,
+            shape: polygon,
+            material: None,
+        }]);
+        assert_eq!(compound.bounding_radius(), 7.0);
+    }
 }
@@ -0,0 +1,48 @@
+use ron;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// failure to read or write a content or save file
+#[derive(Debug)]
+pub enum DataError {
+    Io(io::Error),
+    Parse(ron::de::Error),
+    Write(ron::ser::Error),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataError::Io(e) => write!(f, "file error: {}", e),
+            DataError::Parse(e) => write!(f, "can't parse file: {}", e),
+            DataError::Write(e) => write!(f, "can't serialize data: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for DataError {
+    fn from(e: io::Error) -> Self {
+        DataError::Io(e)
+    }
+}
+
+impl From<ron::de::Error> for DataError {
+    fn from(e: ron::de::Error) -> Self {
+        DataError::Parse(e)
+    }
+}
+
+impl From<ron::ser::Error> for DataError {
+    fn from(e: ron::ser::Error) -> Self {
+        DataError::Write(e)
+    }
+}
+
+// reads a RON file
+pub fn load<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, DataError> {
+    let file = File::open(path)?;
+    Ok(ron::de::from_reader(file)?)
+}
@@ -1,24 +1,116 @@
+pub mod bindings;
+pub mod camera;
+mod collision;
 pub mod components;
+pub mod data;
+mod parallax;
+pub mod physics;
+pub mod polygon;
+pub mod prefabs;
+pub mod raster;
+pub mod render;
+pub mod replay;
+mod save;
+pub mod spatial;
+pub mod sprites;
 mod systems;
+pub mod waves;
 
-use rand::random;
-use specs::shred::{FetchMut, Resource};
+use consts;
+use shrev::EventChannel;
+use specs::shred::{Fetch, FetchMut, Resource};
 use specs::*;
+use std::f64::consts::PI;
+use std::fs;
+use std::mem;
+use std::path::Path;
 
 pub struct Game<'a, 'b> {
     pub world: World,
     dispatcher: Dispatcher<'a, 'b>,
+    event_reader: ReaderId<components::GameEvent>,
+    // events from the last update
+    events: Vec<components::GameEvent>,
+
+    timestep: Timestep,
+    // real time not yet simulated by a tick
+    accumulator: f64,
+
+    // no ticks run while paused
+    paused: bool,
+
+    recording: Option<replay::Recording>,
+}
+
+impl<'a, 'b> Default for Game<'a, 'b> {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+// fixed simulation rate used by `Game::update`
+#[derive(Clone, Copy, Debug)]
+pub struct Timestep {
+    // ticks per s
+    pub tick_rate: f64,
+
+    // most ticks one `update` runs, more time than that is dropped so a slow frame can't snowball
+    pub max_ticks: u32,
+}
+
+impl Default for Timestep {
+    fn default() -> Self {
+        Timestep {
+            tick_rate: consts::TICK_RATE,
+            max_ticks: consts::MAX_TICKS_PER_UPDATE,
+        }
+    }
+}
+
+impl Timestep {
+    // s per tick
+    pub fn delta(&self) -> f64 {
+        1.0 / self.tick_rate
+    }
 }
 
 impl<'a, 'b> Game<'a, 'b> {
     pub fn new() -> Self {
+        let world = components::create_world();
+        let event_reader = world
+            .write_resource::<EventChannel<components::GameEvent>>()
+            .register_reader();
         Game {
-            world: components::create_world(),
+            world,
+            event_reader,
+            events: Vec::new(),
+            timestep: Timestep::default(),
+            accumulator: 0.0,
+            paused: false,
+            recording: None,
             dispatcher: DispatcherBuilder::new()
-                .with(systems::InputSys, "Input", &[])
-                .with(systems::AccelerationSys, "Acceleration", &[])
-                .with(systems::LinearMovementSys, "Linear Movement", &[])
-                .with(systems::AngularMovementSys, "Angular Movement", &[])
+                .with(systems::SnapshotSys, "Snapshot", &[])
+                .with(systems::InputSys, "Input", &["Snapshot"])
+                .with(systems::WaveSys, "Waves", &["Input"])
+                .with(systems::AiSys, "AI", &["Input"])
+                .with(systems::WeaponSys, "Weapon", &["Input", "AI"])
+                .with(systems::LifetimeSys, "Lifetime", &[])
+                .with(systems::MassSys, "Mass", &[])
+                .with(systems::AccelerationSys, "Acceleration", &["Mass"])
+                .with(systems::LinearMovementSys, "Linear Movement", &["Snapshot"])
+                .with(systems::AngularMovementSys, "Angular Movement", &["Snapshot"])
+                .with(systems::CameraSys, "Camera", &["Linear Movement"])
+                .with(
+                    systems::SpatialIndexSys,
+                    "Spatial Index",
+                    &["Linear Movement", "Angular Movement"],
+                ).with(systems::CollisionSys, "Collision", &["Spatial Index"])
+                .with(
+                    systems::CollisionResponseSys,
+                    "Collision Response",
+                    &["Collision", "Mass"],
+                ).with(systems::DamageSys, "Damage", &["Collision"])
+                .with(systems::ProjectileSys, "Projectile", &["Damage"])
                 .build(),
         }
     }
@@ -31,37 +123,295 @@ impl<'a, 'b> Game<'a, 'b> {
         self.world.write_resource()
     }
 
+    pub fn set_timestep(&mut self, timestep: Timestep) {
+        self.timestep = timestep;
+    }
+
+    // runs as many fixed ticks as fit into `dt` s of real time
+    // leftover time carries over to the next update and sets `Interpolation`
     pub fn update(&mut self, dt: f64) {
+        // checked here rather than in a system, as systems don't run while paused
+        let presses = mem::replace(
+            &mut self.world.write_resource::<components::Input>().pause_presses,
+            0,
+        );
+        if presses % 2 == 1 {
+            self.paused = !self.paused;
+        }
+
+        let speed = if self.paused {
+            0.0
+        } else {
+            self.world.read_resource::<components::Clock>().simulation_speed
+        };
+        let delta = self.timestep.delta();
+        self.accumulator += dt * speed;
+        self.events.clear();
+
+        let mut ticks = 0;
+        while self.accumulator >= delta {
+            if ticks == self.timestep.max_ticks {
+                // too far behind, slow down instead of trying to catch up
+                self.accumulator %= delta;
+                break;
+            }
+            self.step();
+            self.accumulator -= delta;
+            ticks += 1;
+        }
+
+        self.world.write_resource::<components::Interpolation>().alpha = self.accumulator / delta;
+    }
+
+    // advances the world by exactly one timestep, for driving the game without real time
+    pub fn tick(&mut self) {
+        self.events.clear();
+        self.step();
+    }
+
+    fn step(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(&self.world.read_resource::<components::Input>());
+        }
+
         // andvance game clock
-        (*self.world.write_resource::<components::Clock>()).advance(dt);
+        (*self.world.write_resource::<components::Clock>()).advance(self.timestep.delta());
 
         // update the world
         self.dispatcher.dispatch(&self.world.res);
 
         // process events generated by systems
-        // TODO
+        let events: Vec<_> = self
+            .world
+            .read_resource::<EventChannel<components::GameEvent>>()
+            .read(&mut self.event_reader)
+            .cloned()
+            .collect();
+        for event in &events {
+            self.handle_event(event);
+        }
+        self.events.extend(events);
+
+        // scrolling happens once, unlike held keys and buttons
+        self.world.write_resource::<components::Input>().mouse_scroll = [0.0, 0.0];
 
         // process async entity creation/deletion
         self.world.maintain();
     }
 
-    pub fn render(&mut self, rendering_dispatcher: &mut Dispatcher) {
-        rendering_dispatcher.dispatch(&self.world.res);
+    fn handle_event(&mut self, event: &components::GameEvent) {
+        use self::components::GameEvent::*;
+        use self::components::Player;
+
+        let player = self.world.read_resource::<Option<Player>>().as_ref().map(|p| p.0);
+        let mut camera = self.world.write_resource::<camera::Camera>();
+        match *event {
+            // shake the screen when the player is hurt, and a little when it kills
+            Hit { target, damage, .. } if Some(target) == player => {
+                camera.add_trauma((damage / 40.0).min(0.6))
+            }
+            Destroyed { killer, .. } if killer.is_some() && killer == player => {
+                camera.add_trauma(0.15)
+            }
+            _ => {}
+        }
+
+        if let Destroyed { entity, .. } = *event {
+            if Some(entity) == player {
+                // the player is gone, stop controlling it
+                camera.add_trauma(1.0);
+                *self.world.write_resource::<Option<Player>>() = None;
+            }
+        }
+    }
+
+    // events generated by the last `update` or `tick`
+    pub fn events(&self) -> &[components::GameEvent] {
+        &self.events
+    }
+
+    // replaces the current prefabs with the ones from a RON file
+    pub fn load_prefabs<P: AsRef<Path>>(&mut self, path: P) -> Result<(), data::DataError> {
+        let prefabs = prefabs::Prefabs::load(path)?;
+        self.add_resource(prefabs);
+        Ok(())
+    }
+
+    // replaces the current sprites with the PNG files in a directory
+    pub fn load_sprites<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), data::DataError> {
+        let sprites = sprites::SpriteAtlas::load(dir)?;
+        self.add_resource(sprites);
+        Ok(())
+    }
+
+    pub fn sprites(&self) -> Fetch<'_, sprites::SpriteAtlas> {
+        self.world.read_resource()
+    }
+
+    // replaces the current waves with the ones from a RON file
+    pub fn load_waves<P: AsRef<Path>>(&mut self, path: P) -> Result<(), data::DataError> {
+        let waves: Vec<waves::Wave> = data::load(path)?;
+        self.add_resource(waves::WaveDirector::new(waves));
+        Ok(())
+    }
+
+    // writes the whole world into a RON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), data::DataError> {
+        fs::write(path, save::save(&self.world)?)?;
+        Ok(())
+    }
+
+    // replaces the whole world with the one from a file written by `save`
+    // stops recording, replays can't start from a loaded world
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), data::DataError> {
+        let source = fs::read_to_string(path)?;
+        save::load(&mut self.world, &source)?;
+        self.recording = None;
+
+        // events and collisions point at entities that are gone now
+        let mut channel = EventChannel::<components::GameEvent>::new();
+        self.event_reader = channel.register_reader();
+        self.world.add_resource(channel);
+        self.world.add_resource(components::Collisions::default());
+        // the grid only gets rebuilt by the next tick, draw the loaded entities before that
+        systems::SpatialIndexSys.run_now(&self.world.res);
+        self.events.clear();
+        self.accumulator = 0.0;
+        self.world.write_resource::<components::Interpolation>().alpha = 0.0;
+        Ok(())
+    }
+
+    // records the input of every following tick, see `replay::Recording`
+    pub fn start_recording(&mut self) {
+        self.recording = Some(replay::Recording::new(self.seed(), self.timestep.tick_rate));
+    }
+
+    // the recording so far, `None` if the game isn't recording
+    pub fn stop_recording(&mut self) -> Option<replay::Recording> {
+        let mut recording = self.recording.take()?;
+        recording.hash = Some(replay::world_hash(&self.world));
+        Some(recording)
+    }
+
+    // what to draw this frame, see `render::DrawList`
+    pub fn draw_list(&self) -> render::DrawList {
+        render::build_draw_list(&self.world)
+    }
+
+    // draws the current frame without a GPU and saves it as PNG
+    pub fn screenshot<P: AsRef<Path>>(&self, path: P) -> Result<(), data::DataError> {
+        let list = self.draw_list();
+        raster::draw(&list, &self.sprites()).save_png(path)
+    }
+
+    // loads the content files and creates the player, every run starts like this
+    pub fn start(&mut self) {
+        if let Err(e) = self.load_prefabs(consts::PREFABS_PATH) {
+            println!("Failed to load prefabs from {}: {}", consts::PREFABS_PATH, e);
+        }
+        self.create_player();
+        if let Err(e) = self.load_waves(consts::WAVES_PATH) {
+            println!("Failed to load waves from {}: {}", consts::WAVES_PATH, e);
+        }
+        if let Err(e) = self.load_sprites(consts::SPRITES_PATH) {
+            println!("Failed to load sprites from {}: {}", consts::SPRITES_PATH, e);
+        }
     }
 
     pub fn create_player(&mut self) {
-        let player_entity = self
+        match self.spawn_prefab("player", components::Point::new(0.0, 0.0)) {
+            Some(player_entity) => {
+                (*self.world.write_resource::<Option<components::Player>>()) =
+                    Some(components::Player(player_entity))
+            }
+            None => println!("Failed to create player: no \"player\" prefab"),
+        }
+    }
+
+    // builds an entity from the named prefab, `None` if there's no such prefab
+    pub fn spawn_prefab(&mut self, name: &str, position: components::Point) -> Option<Entity> {
+        let prefab = self
             .world
-            .create_entity()
-            .with(components::Position(components::Point::new(0.0, 0.0)))
-            .with(components::Rotation::default())
-            .with(components::Velocity::default())
-            .with(components::Acceleration::default())
-            .with(components::AngularVelocity::default())
-            .with(player_shape())
-            .build();
-        (*self.world.write_resource::<Option<components::Player>>()) =
-            Some(components::Player(player_entity));
+            .read_resource::<prefabs::Prefabs>()
+            .get(name)?
+            .clone();
+        Some(prefabs::build_prefab(&prefab, self.world.create_entity(), position).build())
+    }
+
+    // pushes `entity` at the world point `point` during the next tick, needs a `RigidBody`
+    pub fn apply_force(
+        &mut self,
+        entity: Entity,
+        force: components::Vector,
+        point: components::Point,
+    ) {
+        let center = match self.world.read_storage::<components::Position>().get(entity) {
+            Some(pos) => pos.0,
+            None => {
+                println!("Failed to apply force: {:?} has no position", entity);
+                return;
+            }
+        };
+        let mut force_storage = self.world.write_storage::<components::Force>();
+        if force_storage.get(entity).is_none() {
+            if let Err(e) = force_storage.insert(entity, components::Force::default()) {
+                println!("Failed to apply force: {:?}", e);
+                return;
+            }
+        }
+        if let Some(total) = force_storage.get_mut(entity) {
+            total.add_at(force, point, center);
+        }
+    }
+
+    // changes the velocity of `entity` at once, as if hit at the world point `point`
+    pub fn apply_impulse(
+        &mut self,
+        entity: Entity,
+        impulse: components::Vector,
+        point: components::Point,
+    ) {
+        let body_storage = self.world.read_storage::<components::RigidBody>();
+        let shape_storage = self.world.read_storage::<components::Shape>();
+        let pos_storage = self.world.read_storage::<components::Position>();
+        let rot_storage = self.world.read_storage::<components::Rotation>();
+        let mut vel_storage = self.world.write_storage::<components::Velocity>();
+        let mut angular_storage = self.world.write_storage::<components::AngularVelocity>();
+        match (
+            body_storage.get(entity),
+            shape_storage.get(entity),
+            pos_storage.get(entity),
+            vel_storage.get_mut(entity),
+        ) {
+            (Some(body), Some(shape), Some(pos), Some(vel)) => {
+                let angular = angular_storage.get_mut(entity);
+                let mut body = physics::Body::new(
+                    pos.0,
+                    rot_storage.get(entity).map_or(0.0, |rot| rot.0),
+                    vel.0,
+                    angular.as_ref().map_or(0.0, |angular| angular.0),
+                    &components::Mass::new(shape, body),
+                );
+                body.apply_impulse(impulse, point);
+                vel.0 = body.velocity_at(pos.0);
+                if let Some(angular) = angular {
+                    angular.0 = body.angular_velocity;
+                }
+            }
+            _ => println!(
+                "Failed to apply impulse: {:?} needs a rigid body, shape, position and velocity",
+                entity
+            ),
+        }
+    }
+
+    // restarts the random number generator from `seed`
+    pub fn set_seed(&mut self, seed: u64) {
+        self.add_resource(components::Random::new(seed));
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.world.read_resource::<components::Random>().seed()
     }
 
     pub fn input_mut(&mut self) -> FetchMut<components::Input> {
@@ -69,66 +419,73 @@ impl<'a, 'b> Game<'a, 'b> {
     }
 }
 
-pub fn create_dummy_entity<B: Builder>(builder: B) -> B {
+pub fn create_dummy_entity<B: Builder>(builder: B, random: &mut components::Random) -> B {
     // create a dummy "particle"
     const MAX_V: f64 = 20.0;
     builder
         .with(components::Position(components::Point::new(0.0, 0.0)))
         .with(components::Rotation::default())
         .with(components::Velocity::new(
-            random_range(-MAX_V, MAX_V),
-            random_range(-MAX_V, MAX_V),
-        )).with(components::AngularVelocity::new(random_range(-3.14, 3.14)))
-        .with(random_shape())
+            random.range(-MAX_V, MAX_V),
+            random.range(-MAX_V, MAX_V),
+        )).with(components::AngularVelocity::new(random.range(-PI, PI)))
+        .with(random_shape(random))
+        .with(components::RigidBody::default())
+        .with(components::Health::new(DUMMY_HIT_POINTS, 0.0))
 }
 
-fn random_range(from: f64, to: f64) -> f64 {
-    from + (to - from) * random::<f64>()
+pub fn create_projectile<B: Builder>(
+    builder: B,
+    owner: Entity,
+    weapon: &components::Weapon,
+    position: components::Point,
+    rotation: f64,
+    velocity: components::Vector,
+    random: &mut components::Random,
+) -> B {
+    use self::components::*;
+
+    let direction = rotation + random.range(-weapon.spread, weapon.spread);
+    builder
+        .with(Position(position + weapon.muzzle.rotated(rotation)))
+        .with(Rotation(direction))
+        .with(Velocity(
+            velocity + Vector::new(weapon.projectile_speed, 0.0).rotated(direction),
+        )).with(Shape::Circle(PROJECTILE_SIZE))
+        .with(Material {
+            fill: Some(PROJECTILE_COLOR),
+            layer: render::layers::PROJECTILES,
+            ..Material::default()
+        }).with(PROJECTILE_BODY)
+        .with(Projectile { owner: Some(owner) })
+        .with(Damage(weapon.damage))
+        .with(Lifetime(weapon.projectile_lifetime))
 }
 
 const SHAPE_SIZE: f64 = 1.0;
+const PROJECTILE_SIZE: f64 = 0.15;
+const PROJECTILE_COLOR: render::Color = [1.0, 0.9, 0.3, 1.0];
+// heavy enough to knock small ships back a little, gone on impact anyway
+const PROJECTILE_BODY: components::RigidBody = components::RigidBody {
+    density: 2.0,
+    restitution: 0.0,
+    friction: 0.0,
+    fixed_rotation: true,
+};
+const DUMMY_HIT_POINTS: f64 = 20.0;
 
-fn player_shape() -> components::Shape {
+fn random_shape(random: &mut components::Random) -> components::Shape {
     use self::components::Shape::*;
     use self::components::{SubShape, Vector};
 
-    let mut subshapes = Vec::new();
-    subshapes.push(SubShape {
-        offset: Vector::new(0.0, 0.0),
-        rotation: 0.0,
-        shape: Circle(1.0),
-    });
-    subshapes.push(SubShape {
-        offset: Vector::new(0.75, 0.0),
-        rotation: 0.0,
-        shape: Rectangle(Vector::new(0.75, 0.5)),
-    });
-    subshapes.push(SubShape {
-        offset: Vector::new(-0.75, 0.75),
-        rotation: 3.14 / 4.0,
-        shape: Rectangle(Vector::new(0.5, 0.5)),
-    });
-    subshapes.push(SubShape {
-        offset: Vector::new(-0.75, -0.75),
-        rotation: 3.14 / 4.0,
-        shape: Rectangle(Vector::new(0.5, 0.5)),
-    });
-
-    Compound(subshapes)
-}
-
-fn random_shape() -> components::Shape {
-    use self::components::Shape::*;
-    use self::components::{SubShape, Vector};
-
-    match (random::<bool>(), random::<bool>()) {
+    match (random.coin_flip(), random.coin_flip()) {
         (true, true) => Circle(SHAPE_SIZE / 2.0),
         (true, false) => Rectangle(Vector {
             dx: SHAPE_SIZE,
             dy: SHAPE_SIZE,
         }),
         (false, true) => Sprite(
-            "TODO".to_string(),
+            "dummy".to_string(),
             Vector {
                 dx: SHAPE_SIZE,
                 dy: SHAPE_SIZE,
@@ -139,14 +496,43 @@ fn random_shape() -> components::Shape {
             for _ in 0..3 {
                 subshapes.push(SubShape {
                     offset: Vector {
-                        dx: random_range(-SHAPE_SIZE * 0.75, SHAPE_SIZE * 0.75),
-                        dy: random_range(-SHAPE_SIZE * 0.75, SHAPE_SIZE * 0.75),
+                        dx: random.range(-SHAPE_SIZE * 0.75, SHAPE_SIZE * 0.75),
+                        dy: random.range(-SHAPE_SIZE * 0.75, SHAPE_SIZE * 0.75),
                     },
-                    rotation: random_range(0.0, 3.14),
-                    shape: random_shape(),
+                    rotation: random.range(0.0, PI),
+                    shape: random_shape(random),
+                    material: None,
                 })
             }
             Compound(subshapes)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // spawns dummies and random enemies for a while
+    fn spawning_game(seed: u64) -> Game<'static, 'static> {
+        let mut game = Game::new();
+        game.set_seed(seed);
+        game.create_player();
+        for tick in 0..20 {
+            game.input_mut().secondary = true;
+            game.input_mut().spawn_enemy = tick % 2 == 0;
+            game.tick();
+        }
+        game
+    }
+
+    #[test]
+    fn same_seeds_spawn_the_same() {
+        let (a, b) = (spawning_game(11), spawning_game(11));
+        assert_eq!(a.world.entities().join().count(), 31);
+        let save = |game: &Game| save::save(&game.world).unwrap();
+        assert_eq!(save(&a), save(&b));
+        assert_eq!(replay::world_hash(&a.world), replay::world_hash(&b.world));
+        assert_ne!(save(&spawning_game(12)), save(&a));
+    }
+}
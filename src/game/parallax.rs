@@ -0,0 +1,111 @@
+use consts;
+use game::components::{Point, Vector};
+use game::render::{Color, View};
+
+// background stars on planes far behind the game, the further the plane the slower it moves
+pub struct Parallax;
+
+impl Parallax {
+    const PLANES: usize = 4;
+    const ZOOM_STEPS_PER_PLANE: f64 = 10.0;
+    const SECTOR_SIZE: f64 = 100.0;
+    const STARS_PER_SECTOR: usize = 6;
+    const STAR_RADIUS: f64 = 0.05;
+
+    pub fn new() -> Self {
+        Parallax
+    }
+
+    // calls `draw_star(position, radius, colour)` for every visible star
+    // stars are placed in world coords, as they'd appear seen through the game camera
+    pub fn draw<F>(&self, view: &View, mut draw_star: F)
+    where
+        F: FnMut(Point, f64, Color),
+    {
+        let (center, zoom) = (view.center, view.zoom);
+        // stars are the same size on screen in every plane
+        let radius = Parallax::STAR_RADIUS * consts::DEFAULT_ZOOM / zoom;
+        for plane in 0..Parallax::PLANES {
+            let zoom_change =
+                consts::ZOOM_FACTOR.powf(plane as f64 * Parallax::ZOOM_STEPS_PER_PLANE);
+            let plane_zoom = zoom / zoom_change;
+            self.draw_parallax_points(center, plane_zoom, view.size, plane, |p, c| {
+                draw_star(center + (p - center) * (1.0 / zoom_change), radius, c)
+            });
+        }
+    }
+
+    fn draw_parallax_points<F>(
+        &self,
+        center: Point,
+        zoom: f64,
+        size: Vector,
+        plane: usize,
+        mut draw_fun: F,
+    ) where
+        F: FnMut(Point, Color),
+    {
+        use rand::prng::XorShiftRng;
+        use rand::Rng;
+        use rand::SeedableRng;
+
+        // get the bounds for this plane
+        let half_width = size.dx / 2.0 / zoom;
+        let half_height = size.dy / 2.0 / zoom;
+        let (min_x, max_x) = (center.x - half_width, center.x + half_width);
+        let (min_y, max_y) = (center.y - half_height, center.y + half_height);
+
+        // iterate through visible sectors
+        let mut x = min_x - min_x % Parallax::SECTOR_SIZE - Parallax::SECTOR_SIZE;
+        while x < max_x {
+            let mut y = min_y - min_y % Parallax::SECTOR_SIZE - Parallax::SECTOR_SIZE;
+            while y < max_y {
+                // get pseudo random number generator for this sector
+                let mut random = XorShiftRng::from_seed(self.rng_seed(plane, x, y));
+
+                // generate stars
+                for _ in 0..Parallax::STARS_PER_SECTOR {
+                    // pick random colour component (use the same so it's grey), sqrt to push it into brighter colours, 0.9 to add randomness later
+                    let c = random.gen::<f32>().sqrt() * 0.9;
+                    draw_fun(
+                        // random coords in the sector
+                        Point::new(
+                            x + random.gen::<f64>() * Parallax::SECTOR_SIZE,
+                            y + random.gen::<f64>() * Parallax::SECTOR_SIZE,
+                        ),
+                        // add a random colour tint (still mostly grey)
+                        [
+                            c + random.gen::<f32>() * 0.1,
+                            c + random.gen::<f32>() * 0.1,
+                            c + random.gen::<f32>() * 0.1,
+                            1.0,
+                        ],
+                    );
+                }
+                y += Parallax::SECTOR_SIZE;
+            }
+            x += Parallax::SECTOR_SIZE;
+        }
+    }
+
+    fn rng_seed(&self, p: usize, x: f64, y: f64) -> [u8; 16] {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hasher;
+
+        // hash p, x and y
+        let mut hasher = DefaultHasher::new();
+        hasher.write_usize(p);
+        hasher.write_i64(x.floor() as i64);
+        hasher.write_i64(y.floor() as i64);
+
+        // read bytes of u64 hash
+        let hash_bytes = hasher.finish().to_ne_bytes();
+
+        // hash_bytes is [u8; 8] so copy it twice into an output array
+        let mut output = [0u8; 16];
+        for (from, to) in hash_bytes.iter().chain(&hash_bytes).zip(output.iter_mut()) {
+            *to = *from;
+        }
+        output
+    }
+}
@@ -0,0 +1,311 @@
+use game::components::*;
+use game::polygon;
+use std::f64::consts::PI;
+
+// overlap left alone when pushing bodies apart, so resting bodies don't jitter
+const SLOP: f64 = 0.01;
+// part of the remaining overlap removed every tick
+const CORRECTION: f64 = 0.8;
+
+// mass, center of mass and moment of inertia around it, for `density` per area
+// the center is relative to the shape origin
+pub fn mass_properties(shape: &Shape, density: f64) -> (f64, Vector, f64) {
+    let (mass, moment, inertia) = moments(shape, density);
+    if mass <= 0.0 {
+        return (mass, Vector::default(), inertia);
+    }
+    let center = moment * (1.0 / mass);
+    (mass, center, inertia - mass * center.dot(center))
+}
+
+// mass, mass times the center of mass and moment of inertia, all around the shape origin
+fn moments(shape: &Shape, density: f64) -> (f64, Vector, f64) {
+    match shape {
+        Shape::Circle(radius) => {
+            let mass = density * PI * radius * radius;
+            (mass, Vector::default(), mass * radius * radius / 2.0)
+        }
+        Shape::Rectangle(size) | Shape::Sprite(_, size) => {
+            let mass = density * size.dx * size.dy;
+            (mass, Vector::default(), mass * size.dot(*size) / 12.0)
+        }
+        Shape::Polygon(ref polygon) => (
+            density * polygon::area(polygon.points()),
+            polygon::first_moment(polygon.points()) * density,
+            density * polygon::second_moment(polygon.points()),
+        ),
+        Shape::Polyline(ref points, width) => points
+            .iter()
+            .zip(points.iter().skip(1))
+            .map(|(&a, &b)| {
+                // a thin rectangle around the segment, moved out to its middle
+                let length = (b - a).length();
+                let middle = Vector::new(a.x + b.x, a.y + b.y) * 0.5;
+                let mass = density * length * width;
+                let inertia = mass * (length * length + width * width) / 12.0;
+                (mass, middle * mass, inertia + mass * middle.dot(middle))
+            }).fold((0.0, Vector::default(), 0.0), sum),
+        Shape::Compound(ref subshapes) => subshapes
+            .iter()
+            .map(|subshape| {
+                // turning a part around its own origin doesn't change its inertia
+                let (mass, moment, inertia) = moments(&subshape.shape, density);
+                let (offset, moment) = (subshape.offset, moment.rotated(subshape.rotation));
+                (
+                    mass,
+                    moment + offset * mass,
+                    inertia + 2.0 * offset.dot(moment) + mass * offset.dot(offset),
+                )
+            }).fold((0.0, Vector::default(), 0.0), sum),
+    }
+}
+
+fn sum(a: (f64, Vector, f64), b: (f64, Vector, f64)) -> (f64, Vector, f64) {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+// the moving parts of a rigid body, copied out of its components while it's pushed around
+#[derive(Clone, Copy, Debug)]
+pub struct Body {
+    // center of mass, the body turns around it
+    pub center: Point,
+    // of the center of mass, `velocity_at` the entity's position is the one of `Velocity`
+    pub velocity: Vector,
+    pub angular_velocity: f64,
+    // 0 for bodies that can't move or turn
+    pub inverse_mass: f64,
+    pub inverse_inertia: f64,
+}
+
+impl Body {
+    // for an entity at `position` turned by `rotation`, moving with `velocity`
+    pub fn new(
+        position: Point,
+        rotation: f64,
+        velocity: Vector,
+        angular_velocity: f64,
+        mass: &Mass,
+    ) -> Self {
+        let arm = mass.center.rotated(rotation);
+        Body {
+            center: position + arm,
+            velocity: velocity + arm.perpendicular() * angular_velocity,
+            angular_velocity,
+            inverse_mass: inverse(mass.mass),
+            inverse_inertia: inverse(mass.inertia),
+        }
+    }
+
+    // velocity of the world point `point` as if it were part of the body
+    pub fn velocity_at(&self, point: Point) -> Vector {
+        self.velocity + (point - self.center).perpendicular() * self.angular_velocity
+    }
+
+    // changes the velocity at once, as if hit at the world point `point`
+    pub fn apply_impulse(&mut self, impulse: Vector, point: Point) {
+        self.velocity = self.velocity + impulse * self.inverse_mass;
+        let arm = point - self.center;
+        self.angular_velocity += arm.perpendicular().dot(impulse) * self.inverse_inertia;
+    }
+
+    // how hard it is to change the velocity at `point` along `direction`, as 1 / mass
+    fn inverse_mass_along(&self, point: Point, direction: Vector) -> f64 {
+        let arm = (point - self.center).perpendicular().dot(direction);
+        self.inverse_mass + arm * arm * self.inverse_inertia
+    }
+}
+
+// bounces `a` and `b` off each other where they touch, see `Collision`
+// returns how far to move each of them so they overlap less
+pub fn resolve(
+    a: &mut Body,
+    b: &mut Body,
+    collision: &Collision,
+    restitution: f64,
+    friction: f64,
+) -> (Vector, Vector) {
+    let (normal, point) = (collision.normal, collision.point);
+    let total_inverse_mass = a.inverse_mass + b.inverse_mass;
+    if total_inverse_mass <= 0.0 {
+        return (Vector::default(), Vector::default());
+    }
+
+    // only bodies moving into each other bounce
+    let relative_velocity = b.velocity_at(point) - a.velocity_at(point);
+    let closing_speed = relative_velocity.dot(normal);
+    if closing_speed < 0.0 {
+        let k = a.inverse_mass_along(point, normal) + b.inverse_mass_along(point, normal);
+        let impulse = -(1.0 + restitution) * closing_speed / k;
+        a.apply_impulse(normal * -impulse, point);
+        b.apply_impulse(normal * impulse, point);
+
+        // friction works against sliding, but can't push harder than the bounce
+        let tangent = (relative_velocity - normal * closing_speed).normalized();
+        let k = a.inverse_mass_along(point, tangent) + b.inverse_mass_along(point, tangent);
+        if k > 0.0 {
+            let sliding_speed = (b.velocity_at(point) - a.velocity_at(point)).dot(tangent);
+            let max_friction = friction * impulse;
+            let friction_impulse = (-sliding_speed / k).clamp(-max_friction, max_friction);
+            a.apply_impulse(tangent * -friction_impulse, point);
+            b.apply_impulse(tangent * friction_impulse, point);
+        }
+    }
+
+    // lighter bodies get pushed further
+    let correction = (collision.depth - SLOP).max(0.0) * CORRECTION / total_inverse_mass;
+    (
+        normal * (-correction * a.inverse_mass),
+        normal * (correction * b.inverse_mass),
+    )
+}
+
+// pushes don't move bodies without mass, `CollisionResponseSys` leaves them out of bumps
+fn inverse(x: f64) -> f64 {
+    if x > 0.0 {
+        1.0 / x
+    } else {
+        0.0
+    }
+}
+
+// restitution and friction of two bodies touching, bouncy wins and both have to be slippery
+pub fn mix(a: &RigidBody, b: &RigidBody) -> (f64, f64) {
+    (
+        a.restitution.max(b.restitution),
+        (a.friction * b.friction).sqrt(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::polygon::Polygon;
+    use specs::{Builder, World};
+
+    fn square(corner: Point) -> Shape {
+        let points = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]
+            .iter()
+            .map(|&(x, y)| Point::new(corner.x + x, corner.y + y))
+            .collect();
+        Shape::Polygon(Polygon::new(points))
+    }
+
+    fn assert_mass(shape: &Shape, mass: f64, center: Vector, inertia: f64) {
+        let (m, c, i) = mass_properties(shape, 1.0);
+        assert!((m - mass).abs() < 1e-9, "mass {} instead of {}", m, mass);
+        assert!((c - center).length() < 1e-9, "center {:?} instead of {:?}", c, center);
+        assert!((i - inertia).abs() < 1e-9, "inertia {} instead of {}", i, inertia);
+    }
+
+    #[test]
+    fn inertia_is_around_the_center_of_mass() {
+        let rectangle = Shape::Rectangle(Vector::new(2.0, 2.0));
+        let inertia = 4.0 * 8.0 / 12.0;
+        assert_mass(&rectangle, 4.0, Vector::default(), inertia);
+        assert_mass(&square(Point::new(-1.0, -1.0)), 4.0, Vector::default(), inertia);
+        assert_mass(&square(Point::new(1.0, 1.0)), 4.0, Vector::new(2.0, 2.0), inertia);
+
+        let line = Shape::Polyline(vec![Point::new(0.0, 0.0), Point::new(4.0, 0.0)], 1.0);
+        assert_mass(&line, 4.0, Vector::new(2.0, 0.0), 4.0 * 17.0 / 12.0);
+    }
+
+    #[test]
+    fn compound_parts_are_moved_and_turned() {
+        let part = |offset, rotation| SubShape {
+            offset,
+            rotation,
+            shape: square(Point::new(1.0, -1.0)),
+            material: None,
+        };
+        // a square right of the origin, and one turned to the left of it and moved back
+        let compound = Shape::Compound(vec![
+            part(Vector::default(), 0.0),
+            part(Vector::new(6.0, 0.0), PI),
+        ]);
+        // two 2 x 2 squares side by side, a 4 x 2 rectangle around (3, 0)
+        assert_mass(&compound, 8.0, Vector::new(3.0, 0.0), 8.0 * 20.0 / 12.0);
+    }
+
+    #[test]
+    fn body_turns_around_its_center_of_mass() {
+        let mass = Mass { mass: 1.0, center: Vector::new(1.0, 0.0), inertia: 1.0 };
+        let position = Point::new(5.0, 5.0);
+        let body = Body::new(position, PI / 2.0, Vector::new(1.0, 0.0), 2.0, &mass);
+        assert!((body.center - Point::new(5.0, 6.0)).length() < 1e-9);
+        assert!((body.velocity - Vector::new(-1.0, 0.0)).length() < 1e-9);
+        assert!((body.velocity_at(position) - Vector::new(1.0, 0.0)).length() < 1e-9);
+    }
+
+    fn body(x: f64, velocity: Vector, mass: f64) -> Body {
+        let mass = Mass { mass, center: Vector::default(), inertia: mass };
+        Body::new(Point::new(x, 0.0), 0.0, velocity, 0.0, &mass)
+    }
+
+    // `a` left of `b`, touching at `point`
+    fn collision(point: Point) -> Collision {
+        let mut world = World::new();
+        Collision {
+            a: world.create_entity().build(),
+            b: world.create_entity().build(),
+            normal: Vector::new(1.0, 0.0),
+            depth: 0.5,
+            point,
+        }
+    }
+
+    fn momentum(bodies: &[&Body]) -> Vector {
+        bodies
+            .iter()
+            .fold(Vector::default(), |sum, body| sum + body.velocity * (1.0 / body.inverse_mass))
+    }
+
+    #[test]
+    fn equal_bodies_swap_velocities() {
+        let mut a = body(0.0, Vector::new(3.0, 0.0), 2.0);
+        let mut b = body(2.0, Vector::default(), 2.0);
+        let before = momentum(&[&a, &b]);
+        let (push_a, push_b) = resolve(&mut a, &mut b, &collision(Point::new(1.0, 0.0)), 1.0, 0.5);
+        assert!((a.velocity - Vector::default()).length() < 1e-9, "{:?}", a.velocity);
+        assert!((b.velocity - Vector::new(3.0, 0.0)).length() < 1e-9, "{:?}", b.velocity);
+        assert!((momentum(&[&a, &b]) - before).length() < 1e-9);
+        assert_eq!((a.angular_velocity, b.angular_velocity), (0.0, 0.0));
+        // pushed apart evenly, by most of the overlap
+        assert!(push_a.dx < 0.0 && (push_a + push_b).length() < 1e-9);
+        assert!((push_b.dx - push_a.dx - (0.5 - SLOP) * CORRECTION).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inelastic_bumps_stop_closing_in() {
+        // off center, so both bodies start spinning too
+        let point = Point::new(1.0, 0.5);
+        let mut a = body(0.0, Vector::new(4.0, 1.0), 1.0);
+        let mut b = body(2.0, Vector::default(), 3.0);
+        let before = momentum(&[&a, &b]);
+        resolve(&mut a, &mut b, &collision(point), 0.0, 0.0);
+        let closing_speed = (b.velocity_at(point) - a.velocity_at(point)).dx;
+        assert!(closing_speed.abs() < 1e-9, "still closing in at {}", closing_speed);
+        assert!(a.angular_velocity != 0.0 && b.angular_velocity != 0.0);
+        assert!((momentum(&[&a, &b]) - before).length() < 1e-9);
+    }
+
+    #[test]
+    fn separating_bodies_dont_bounce() {
+        let mut a = body(0.0, Vector::new(-1.0, 0.0), 1.0);
+        let mut b = body(2.0, Vector::default(), 1.0);
+        resolve(&mut a, &mut b, &collision(Point::new(1.0, 0.0)), 1.0, 1.0);
+        assert_eq!((a.velocity.dx, b.velocity.dx), (-1.0, 0.0));
+    }
+
+    #[test]
+    fn walls_dont_move() {
+        let mut a = body(0.0, Vector::new(2.0, 1.0), 1.0);
+        let mut b = body(2.0, Vector::default(), 1.0);
+        b.inverse_mass = 0.0;
+        b.inverse_inertia = 0.0;
+        let (push_a, push_b) = resolve(&mut a, &mut b, &collision(Point::new(1.0, 0.0)), 1.0, 0.0);
+        assert!((a.velocity - Vector::new(-2.0, 1.0)).length() < 1e-9, "{:?}", a.velocity);
+        assert_eq!((b.velocity.dx, b.velocity.dy, b.angular_velocity), (0.0, 0.0, 0.0));
+        assert!(push_a.dx < 0.0);
+        assert_eq!(push_b.dx, 0.0);
+    }
+}
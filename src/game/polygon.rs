@@ -0,0 +1,309 @@
+use game::components::{Point, Vector};
+
+// polygons are lists of points along their outline, in either winding order,
+// with edges between neighbours and from the last point back to the first
+
+// outline points and the triangles filling them, only triangulated once when it's made
+// reads and writes as its list of points
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "Vec<Point>", into = "Vec<Point>")]
+pub struct Polygon {
+    points: Vec<Point>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Point>) -> Self {
+        Polygon {
+            triangles: triangulate(&points),
+            points,
+        }
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    // see `triangulate`
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+}
+
+impl From<Vec<Point>> for Polygon {
+    fn from(points: Vec<Point>) -> Self {
+        Polygon::new(points)
+    }
+}
+
+impl From<Polygon> for Vec<Point> {
+    fn from(polygon: Polygon) -> Self {
+        polygon.points
+    }
+}
+
+// positive for one winding order, negative for the other
+pub fn signed_area(points: &[Point]) -> f64 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f64>()
+        / 2.0
+}
+
+pub fn area(points: &[Point]) -> f64 {
+    signed_area(points).abs()
+}
+
+// area times the centroid, as seen from the origin
+pub fn first_moment(points: &[Point]) -> Vector {
+    let moment = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| Vector::new(a.x + b.x, a.y + b.y) * ((a.x * b.y - b.x * a.y) / 6.0))
+        .fold(Vector::default(), |sum, moment| sum + moment);
+    moment * signed_area(points).signum()
+}
+
+// polar moment of area around the origin (not the centroid), the moment of inertia of
+// a polygon with density 1
+pub fn second_moment(points: &[Point]) -> f64 {
+    let moment: f64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| {
+            let cross = a.x * b.y - b.x * a.y;
+            cross * (a.x * a.x + a.y * a.y + a.x * b.x + a.y * b.y + b.x * b.x + b.y * b.y)
+        }).sum();
+    (moment / 12.0).abs()
+}
+
+// splits a polygon without crossing edges, convex or not, into triangles
+// given as indices into `points`, all in the same winding order as the polygon
+// outlines may touch themselves in a point, like two squares sharing a corner
+pub fn triangulate(points: &[Point]) -> Vec<[usize; 3]> {
+    // clip ears with the same turn as the whole polygon
+    let winding = signed_area(points).signum();
+    let total_area = area(points);
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() >= 3 {
+        let outline: Vec<_> = remaining.iter().map(|&i| points[i]).collect();
+        // outlines touching themselves end in a part folded back without any area
+        if area(&outline) <= total_area * 1e-9 {
+            break;
+        }
+        if remaining.len() == 3 {
+            triangles.push([remaining[0], remaining[1], remaining[2]]);
+            break;
+        }
+
+        let count = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            ]
+        };
+        let turn = |i: usize| {
+            let [a, b, c] = corner(i);
+            cross(points[b] - points[a], points[c] - points[b]) * winding
+        };
+        let is_ear = |i: usize| {
+            let [a, b, c] = corner(i);
+            let (a, b, c) = (points[a], points[b], points[c]);
+            // where the outline touches itself a corner shows up again, that one doesn't count
+            turn(i) > 0.0
+                && !outline
+                    .iter()
+                    .filter(|&&p| !same(p, a) && !same(p, b) && !same(p, c))
+                    .any(|&p| in_triangle(p, a, b, c))
+                && !outline
+                    .iter()
+                    .zip(outline.iter().cycle().skip(1))
+                    .any(|(&p, &q)| cuts(p, q, a, c))
+        };
+        match (0..count).find(|&i| is_ear(i)) {
+            Some(ear) => {
+                triangles.push(corner(ear));
+                remaining.remove(ear);
+            }
+            // straight corners have no area to clip, and degenerate outlines may have no ears
+            // left at all, clip anything so it always finishes
+            None => {
+                let ear = (0..count).find(|&i| turn(i) == 0.0);
+                if ear.is_none() {
+                    triangles.push(corner(0));
+                }
+                remaining.remove(ear.unwrap_or(0));
+            }
+        }
+    }
+    triangles
+}
+
+fn cross(a: Vector, b: Vector) -> f64 {
+    a.dx * b.dy - a.dy * b.dx
+}
+
+fn same(a: Point, b: Point) -> bool {
+    a.x == b.x && a.y == b.y
+}
+
+// works for both windings, points on the edges count as inside
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    let sides = [
+        cross(b - a, p - a),
+        cross(c - b, p - b),
+        cross(a - c, p - c),
+    ];
+    sides.iter().all(|&side| side >= 0.0) || sides.iter().all(|&side| side <= 0.0)
+}
+
+// whether the edge from `p` to `q` crosses the line from `a` to `c`, or touches it anywhere
+// but its ends
+fn cuts(p: Point, q: Point, a: Point, c: Point) -> bool {
+    let crosses = cross(c - a, p - a) * cross(c - a, q - a) < 0.0
+        && cross(q - p, a - p) * cross(q - p, c - p) < 0.0;
+    let touches = |v: Point| {
+        !same(v, a)
+            && !same(v, c)
+            && cross(c - a, v - a) == 0.0
+            && (v - a).dot(c - a) >= 0.0
+            && (v - c).dot(a - c) >= 0.0
+    };
+    crosses || touches(p) || touches(q)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coords: &[(f64, f64)]) -> Vec<Point> {
+        coords.iter().map(|&(x, y)| Point::new(x, y)).collect()
+    }
+
+    // even-odd rule, only used away from the edges
+    fn inside(p: Point, points: &[Point]) -> bool {
+        points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .filter(|&(a, b)| {
+                (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x)
+            }).count()
+            % 2
+            == 1
+    }
+
+    // the triangles have to cover the polygon exactly, without sticking out of it
+    fn assert_fills(points: &[Point]) {
+        let triangles = triangulate(points);
+        let mut total = 0.0;
+        for triangle in &triangles {
+            let corners: Vec<_> = triangle.iter().map(|&i| points[i]).collect();
+            let area = signed_area(&corners);
+            assert!(area * signed_area(points) >= 0.0, "{:?} turns the wrong way", triangle);
+            if area != 0.0 {
+                let (a, b, c) = (corners[0], corners[1], corners[2]);
+                let center = Point::new((a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0);
+                assert!(inside(center, points), "{:?} is outside", triangle);
+            }
+            total += area;
+        }
+        assert!((total - signed_area(points)).abs() < 1e-9);
+    }
+
+    fn reversed(points: &[Point]) -> Vec<Point> {
+        points.iter().rev().cloned().collect()
+    }
+
+    #[test]
+    fn convex_quad() {
+        let quad = points(&[(0.0, 0.0), (2.0, 0.0), (3.0, 2.0), (-1.0, 1.0)]);
+        assert_eq!(triangulate(&quad).len(), 2);
+        assert_fills(&quad);
+        assert_fills(&reversed(&quad));
+    }
+
+    #[test]
+    fn concave_arrow() {
+        // the strafer hull
+        let arrow = points(&[(1.0, 0.0), (-0.8, 0.9), (-0.3, 0.0), (-0.8, -0.9)]);
+        assert_eq!(triangulate(&arrow).len(), 2);
+        assert_fills(&arrow);
+        assert_fills(&reversed(&arrow));
+    }
+
+    #[test]
+    fn collinear_points() {
+        // a square with points in the middle of its edges
+        let square = points(&[
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (2.0, 2.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 1.0),
+        ]);
+        assert_fills(&square);
+        assert_fills(&reversed(&square));
+
+        // a U whose inner corners lie on the lines between its outer ones
+        let u = points(&[
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 3.0),
+            (2.0, 3.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+        ]);
+        assert_fills(&u);
+        assert_fills(&reversed(&u));
+    }
+
+    #[test]
+    fn touching_corners() {
+        // two squares meeting in one corner, which is on the outline twice
+        let squares = points(&[
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 1.0),
+            (2.0, 2.0),
+            (1.0, 2.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+        ]);
+        assert_fills(&squares);
+        assert_fills(&reversed(&squares));
+    }
+
+    #[test]
+    fn outline_touching_itself() {
+        // a pointy loop and a triangle sharing a corner
+        let loops = points(&[
+            (3.0, 3.0),
+            (2.0, 4.0),
+            (0.0, 4.0),
+            (1.0, 2.0),
+            (1.0, 1.0),
+            (2.0, 1.0),
+            (3.0, 3.0),
+            (6.0, 0.0),
+            (6.0, 4.0),
+        ]);
+        assert_fills(&loops);
+        assert_fills(&reversed(&loops));
+    }
+
+    #[test]
+    fn too_few_points() {
+        assert!(triangulate(&points(&[(0.0, 0.0), (1.0, 0.0)])).is_empty());
+    }
+}
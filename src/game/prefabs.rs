@@ -0,0 +1,140 @@
+use game::components::*;
+use game::data;
+use ron;
+use specs::Builder;
+use std::collections::HashMap;
+use std::path::Path;
+
+// prefabs compiled into the game, used until `Game::load_prefabs` replaces them
+const BUILTIN_PREFABS: &str = include_str!("../../assets/prefabs.ron");
+
+// one of the components a prefab entity is built with
+#[derive(Clone, Debug, Deserialize)]
+pub enum PrefabComponent {
+    Shape(Shape),
+    Material(Material),
+    Velocity(Vector),
+    Acceleration(Vector),
+    AngularVelocity(f64),
+    FlightModel(FlightModel),
+    RigidBody(RigidBody),
+    Health { hit_points: f64, shield: f64 },
+    Damage(f64),
+    Weapon(Weapon),
+    Ai(AiBehaviour),
+}
+
+pub type Prefab = Vec<PrefabComponent>;
+
+// entity blueprints by name
+#[derive(Default)]
+pub struct Prefabs(HashMap<String, Prefab>);
+
+impl Prefabs {
+    pub fn builtin() -> Self {
+        Prefabs(ron::de::from_str(BUILTIN_PREFABS).expect("built-in prefabs are broken"))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, data::DataError> {
+        Ok(Prefabs(data::load(path)?))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.0.get(name)
+    }
+
+    // names of the prefabs driven by AI, sorted
+    pub fn enemy_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(_, prefab)| {
+                prefab
+                    .iter()
+                    .any(|c| matches!(c, PrefabComponent::Ai(_)))
+            }).map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+pub fn build_prefab<B: Builder>(prefab: &Prefab, builder: B, position: Point) -> B {
+    let builder = builder
+        .with(Position(position))
+        .with(Rotation::default());
+    prefab
+        .iter()
+        .cloned()
+        .fold(builder, |builder, component| match component {
+            PrefabComponent::Shape(shape) => builder.with(shape),
+            PrefabComponent::Material(material) => builder.with(material),
+            PrefabComponent::Velocity(v) => builder.with(Velocity(v)),
+            PrefabComponent::Acceleration(a) => builder.with(Acceleration(a)),
+            PrefabComponent::AngularVelocity(dr) => builder.with(AngularVelocity(dr)),
+            PrefabComponent::FlightModel(flight) => builder.with(flight),
+            PrefabComponent::RigidBody(body) => builder.with(body),
+            PrefabComponent::Health { hit_points, shield } => {
+                builder.with(Health::new(hit_points, shield))
+            }
+            PrefabComponent::Damage(damage) => builder.with(Damage(damage)),
+            PrefabComponent::Weapon(weapon) => builder.with(weapon),
+            PrefabComponent::Ai(ai) => builder.with(ai),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::World;
+
+    #[test]
+    fn polygons_load_from_prefabs() {
+        let source = r#"{
+            "arrow": [
+                Shape(Polygon([
+                    (x: 1.0, y: 0.0),
+                    (x: -0.8, y: 0.9),
+                    (x: -0.3, y: 0.0),
+                    (x: -0.8, y: -0.9),
+                ])),
+                RigidBody(()),
+            ],
+            "wire": [Shape(Polyline([(x: 0.0, y: 0.0), (x: 2.0, y: 0.0), (x: 2.0, y: 1.0)], 0.2))],
+        }"#;
+        let prefabs = Prefabs(ron::de::from_str(source).unwrap());
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<Rotation>();
+        world.register::<Shape>();
+        world.register::<RigidBody>();
+
+        let arrow = build_prefab(
+            prefabs.get("arrow").unwrap(),
+            world.create_entity(),
+            Point::new(3.0, 4.0),
+        ).build();
+        let shape = world.read_storage::<Shape>().get(arrow).unwrap().clone();
+        match shape {
+            Shape::Polygon(ref polygon) => {
+                assert_eq!(polygon.points().len(), 4);
+                // concave, so one specific split into two triangles
+                assert_eq!(polygon.triangles().len(), 2);
+            }
+            ref shape => panic!("arrow is a {:?}", shape),
+        }
+        assert!(world.read_storage::<RigidBody>().get(arrow).is_some());
+
+        // and writes back as the same list of points, with the same triangles
+        let again: Shape = ron::de::from_str(&ron::ser::to_string(&shape).unwrap()).unwrap();
+        assert_eq!(format!("{:?}", again), format!("{:?}", shape));
+
+        match prefabs.get("wire").unwrap()[0] {
+            PrefabComponent::Shape(Shape::Polyline(ref points, width)) => {
+                assert_eq!(points.len(), 3);
+                assert_eq!(width, 0.2);
+            }
+            ref component => panic!("wire is a {:?}", component),
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use game::components::{Point, Vector};
+use game::data::DataError;
+use game::render::{Color, DrawCommand, DrawList, Primitive, View};
+use game::sprites::{Region, SpriteAtlas};
+use png::{self, HasParameters};
+use std::f64::consts::FRAC_1_SQRT_2;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+// draws `list` on the CPU the way the windowed frontends do, at the size of its view
+// for screenshots and for comparing frames without a GPU
+pub fn draw(list: &DrawList, sprites: &SpriteAtlas) -> Image {
+    let mut image = Image::new(
+        list.view.size.dx.round() as u32,
+        list.view.size.dy.round() as u32,
+        list.background,
+    );
+    let view = &list.view;
+    for command in &list.commands {
+        let center = view.to_screen(command.position);
+        match command.primitive {
+            Primitive::Circle { radius } => {
+                image.fill_circle(center, radius * view.zoom, command.color);
+                if let Some(outline) = command.outline {
+                    image.stroke_circle(
+                        center,
+                        radius * view.zoom,
+                        outline.width * view.zoom,
+                        outline.color,
+                    );
+                }
+            }
+            Primitive::Rectangle { size } => {
+                let corners = corners(view, command, size);
+                image.fill_polygon(&corners, command.color);
+                if let Some(outline) = command.outline {
+                    image.stroke_polygon(&corners, outline.width * view.zoom, outline.color);
+                }
+            }
+            Primitive::Sprite { size, region, .. } => {
+                image.draw_sprite(
+                    sprites.image(),
+                    region,
+                    center,
+                    size * view.zoom,
+                    command.rotation,
+                    command.color,
+                );
+                if let Some(outline) = command.outline {
+                    let corners = corners(view, command, size);
+                    image.stroke_polygon(&corners, outline.width * view.zoom, outline.color);
+                }
+            }
+            Primitive::Polygon {
+                ref points,
+                ref triangles,
+            } => {
+                let points: Vec<_> = points.iter().map(|&p| place(view, command, p)).collect();
+                for triangle in triangles {
+                    let corners: Vec<_> = triangle.iter().map(|&i| points[i]).collect();
+                    image.fill_polygon(&corners, command.color);
+                }
+                if let Some(outline) = command.outline {
+                    image.stroke_polygon(&points, outline.width * view.zoom, outline.color);
+                }
+            }
+            Primitive::Polyline { ref points, width } => {
+                let points: Vec<_> = points.iter().map(|&p| place(view, command, p)).collect();
+                image.stroke_polyline(&points, width * view.zoom, command.color);
+            }
+        }
+    }
+    image
+}
+
+// screen coords of a rectangle `size` big placed by `command`
+fn corners(view: &View, command: &DrawCommand, size: Vector) -> Vec<Point> {
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .iter()
+        .map(|&(sx, sy)| place(view, command, Point::new(sx * size.dx / 2.0, sy * size.dy / 2.0)))
+        .collect()
+}
+
+// screen coords of `p` in the coords of the primitive placed by `command`
+fn place(view: &View, command: &DrawCommand, p: Point) -> Point {
+    view.to_screen(command.position + Vector::new(p.x, p.y).rotated(command.rotation))
+}
+
+// RGBA pixels with 8 bits per channel, row by row from the top left corner
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, color: Color) -> Self {
+        let color = to_rgba(color);
+        Image {
+            width,
+            height,
+            pixels: color
+                .iter()
+                .cloned()
+                .cycle()
+                .take((width * height * 4) as usize)
+                .collect(),
+        }
+    }
+
+    // `pixels` are RGBA, row by row
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Self, DataError> {
+        let decoder = png::Decoder::new(File::open(path)?);
+        let (info, mut reader) = decoder.read_info().map_err(io::Error::from)?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).map_err(io::Error::from)?;
+
+        // the decoder already expands palettes and strips 16 bit channels
+        let pixels = match info.color_type {
+            png::ColorType::RGBA => data,
+            png::ColorType::RGB => data
+                .chunks(3)
+                .flat_map(|p| vec![p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks(2)
+                .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => data.iter().flat_map(|&p| vec![p, p, p, 255]).collect(),
+            png::ColorType::Indexed => {
+                let message = "indexed colours weren't expanded";
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+            }
+        };
+        Ok(Image::from_pixels(info.width, info.height, pixels))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = self.index(x, y);
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), DataError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::from)?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    // overwrites pixels starting at (`x`, `y`) with the whole of `image`, which has to fit
+    pub fn copy_from(&mut self, image: &Image, x: u32, y: u32) {
+        let row_length = image.width as usize * 4;
+        for row in 0..image.height {
+            let from = image.index(0, row);
+            let to = self.index(x, y + row);
+            self.pixels[to..to + row_length]
+                .copy_from_slice(&image.pixels[from..from + row_length]);
+        }
+    }
+
+    // draws `region` of `texture` stretched to `size` pixels around `center`, turned by
+    // `rotation` and tinted by `color`
+    pub fn draw_sprite(
+        &mut self,
+        texture: &Image,
+        region: Region,
+        center: Point,
+        size: Vector,
+        rotation: f64,
+        color: Color,
+    ) {
+        if region.width == 0 || region.height == 0 {
+            return;
+        }
+        let half_diagonal = (size * 0.5).length();
+        let min = center + Vector::new(-half_diagonal, -half_diagonal);
+        let max = center + Vector::new(half_diagonal, half_diagonal);
+        let (min_x, max_x) = Image::pixel_range(min.x, max.x, self.width);
+        let (min_y, max_y) = Image::pixel_range(min.y, max.y, self.height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // position inside the sprite, from 0 to 1 on both axes
+                let pixel = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                let local = (pixel - center).rotated(-rotation);
+                let u = local.dx / size.dx + 0.5;
+                let v = local.dy / size.dy + 0.5;
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                let texel = texture.pixel(
+                    region.x + (u * region.width as f64) as u32,
+                    region.y + (v * region.height as f64) as u32,
+                );
+                let mut tinted = color;
+                for (channel, value) in tinted.iter_mut().zip(&texel) {
+                    *channel *= *value as f32 / 255.0;
+                }
+                self.blend(x, y, tinted);
+            }
+        }
+    }
+
+    // a pixel is covered when its center is inside the circle
+    pub fn fill_circle(&mut self, center: Point, radius: f64, color: Color) {
+        // tiny circles still cover at least one pixel
+        let radius = radius.max(FRAC_1_SQRT_2);
+        let min = center + Vector::new(-radius, -radius);
+        let max = center + Vector::new(radius, radius);
+        self.fill_where(min, max, color, |p| (p - center).length() <= radius);
+    }
+
+    // `points` have to make a convex polygon, in either winding order
+    pub fn fill_polygon(&mut self, points: &[Point], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        let min = points
+            .iter()
+            .fold(points[0], |m, p| Point::new(m.x.min(p.x), m.y.min(p.y)));
+        let max = points
+            .iter()
+            .fold(points[0], |m, p| Point::new(m.x.max(p.x), m.y.max(p.y)));
+        self.fill_where(min, max, color, |p| {
+            // inside when it isn't on opposite sides of any two edges
+            let (mut left, mut right) = (false, false);
+            for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                let side = (*b - *a).perpendicular().dot(p - *a);
+                left |= side > 0.0;
+                right |= side < 0.0;
+            }
+            !(left && right)
+        });
+    }
+
+    // line `width` wide centered on the edge of the circle
+    pub fn stroke_circle(&mut self, center: Point, radius: f64, width: f64, color: Color) {
+        // thin lines are still a pixel wide
+        let half_width = (width / 2.0).max(0.5);
+        let outer = radius + half_width;
+        let min = center + Vector::new(-outer, -outer);
+        let max = center + Vector::new(outer, outer);
+        self.fill_where(min, max, color, |p| {
+            ((p - center).length() - radius).abs() <= half_width
+        });
+    }
+
+    // lines `width` wide centered on the edges of the polygon, which can have any shape
+    pub fn stroke_polygon(&mut self, points: &[Point], width: f64, color: Color) {
+        self.stroke(points, true, width, color)
+    }
+
+    // lines `width` wide between neighbouring points
+    pub fn stroke_polyline(&mut self, points: &[Point], width: f64, color: Color) {
+        self.stroke(points, false, width, color)
+    }
+
+    // `closed` adds a line from the last point back to the first
+    fn stroke(&mut self, points: &[Point], closed: bool, width: f64, color: Color) {
+        if points.is_empty() {
+            return;
+        }
+        let segments = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        let half_width = (width / 2.0).max(0.5);
+        let half_size = Vector::new(half_width, half_width);
+        let min = points
+            .iter()
+            .fold(points[0], |m, p| Point::new(m.x.min(p.x), m.y.min(p.y)));
+        let max = points
+            .iter()
+            .fold(points[0], |m, p| Point::new(m.x.max(p.x), m.y.max(p.y)));
+        self.fill_where(min + -half_size, max + half_size, color, |p| {
+            points
+                .iter()
+                .zip(points.iter().cycle().skip(1))
+                .take(segments)
+                .any(|(a, b)| distance_to_segment(p, *a, *b) <= half_width)
+        });
+    }
+
+    // blends `color` into every pixel between `min` and `max` whose center passes `inside`
+    fn fill_where<F>(&mut self, min: Point, max: Point, color: Color, inside: F)
+    where
+        F: Fn(Point) -> bool,
+    {
+        let (min_x, max_x) = Image::pixel_range(min.x, max.x, self.width);
+        let (min_y, max_y) = Image::pixel_range(min.y, max.y, self.height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                if inside(Point::new(x as f64 + 0.5, y as f64 + 0.5)) {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    // pixel index range covering coords from `min` to `max`, limited to `0..limit`
+    fn pixel_range(min: f64, max: f64, limit: u32) -> (u32, u32) {
+        let limit = limit as f64;
+        (
+            min.floor().clamp(0.0, limit) as u32,
+            max.ceil().clamp(0.0, limit) as u32,
+        )
+    }
+
+    // draws `color` over the pixel using its alpha
+    fn blend(&mut self, x: u32, y: u32, color: Color) {
+        let i = self.index(x, y);
+        let alpha = color[3].clamp(0.0, 1.0);
+        for (channel, value) in self.pixels[i..i + 3].iter_mut().zip(&to_rgba(color)) {
+            *channel = (*value as f32 * alpha + *channel as f32 * (1.0 - alpha)).round() as u8;
+        }
+        let previous_alpha = self.pixels[i + 3] as f32;
+        self.pixels[i + 3] = (alpha * 255.0 + previous_alpha * (1.0 - alpha)).round() as u8;
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize * 4
+    }
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f64 {
+    let segment = b - a;
+    let length_squared = segment.dot(segment);
+    if length_squared == 0.0 {
+        return (p - a).length();
+    }
+    // closest point on the segment
+    let t = ((p - a).dot(segment) / length_squared).clamp(0.0, 1.0);
+    (p - (a + segment * t)).length()
+}
+
+fn to_rgba(color: Color) -> [u8; 4] {
+    let mut rgba = [0; 4];
+    for (to, from) in rgba.iter_mut().zip(&color) {
+        *to = (from.clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::components::*;
+    use game::parallax::Parallax;
+    use game::render::{colors, layers};
+    use game::Game;
+    use specs::Builder;
+    use std::env;
+    use std::f64::consts::FRAC_PI_2;
+
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn draw_commands(view: View, commands: Vec<DrawCommand>) -> Image {
+        let list = DrawList {
+            view,
+            background: colors::BLACK,
+            commands,
+        };
+        draw(&list, &SpriteAtlas::default())
+    }
+
+    fn command(primitive: Primitive, position: Point, rotation: f64) -> DrawCommand {
+        DrawCommand {
+            primitive,
+            position,
+            rotation,
+            color: colors::RED,
+            outline: None,
+            layer: layers::ENTITIES,
+        }
+    }
+
+    fn view(center: Point, zoom: f64) -> View {
+        View {
+            center,
+            zoom,
+            size: Vector::new(40.0, 40.0),
+        }
+    }
+
+    // compares with the PNG in tests/golden, set UPDATE_GOLDEN to write it instead
+    fn assert_golden(image: &Image, name: &str) {
+        let path = format!("{}/tests/golden/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            image.save_png(&path).unwrap();
+        }
+        let golden = Image::load_png(&path).unwrap();
+        assert_eq!((image.width(), image.height()), (golden.width(), golden.height()));
+        assert!(image.pixels() == golden.pixels(), "{} differs from {}", name, path);
+    }
+
+    #[test]
+    fn circle() {
+        let circle = command(Primitive::Circle { radius: 1.0 }, Point::new(0.0, 0.0), 0.0);
+        let image = draw_commands(view(Point::new(0.0, 0.0), 10.0), vec![circle]);
+        assert_eq!((image.width(), image.height()), (40, 40));
+        assert_eq!(image.pixel(20, 20), RED);
+        assert_eq!(image.pixel(20, 29), RED);
+        assert_eq!(image.pixel(20, 30), BLACK);
+        assert_eq!(image.pixel(10, 20), RED);
+        assert_eq!(image.pixel(9, 20), BLACK);
+        assert_eq!(image.pixel(27, 27), BLACK);
+    }
+
+    #[test]
+    fn rotated_rectangle() {
+        let size = Vector::new(4.0, 1.0);
+        let rectangle = command(Primitive::Rectangle { size }, Point::new(0.0, 0.0), FRAC_PI_2);
+        let image = draw_commands(view(Point::new(0.0, 0.0), 5.0), vec![rectangle]);
+        // 4 long upright, 1 wide across
+        assert_eq!(image.pixel(20, 11), RED);
+        assert_eq!(image.pixel(20, 28), RED);
+        assert_eq!(image.pixel(20, 8), BLACK);
+        assert_eq!(image.pixel(22, 20), RED);
+        assert_eq!(image.pixel(23, 20), BLACK);
+        assert_eq!(image.pixel(27, 20), BLACK);
+    }
+
+    #[test]
+    fn offset_and_zoomed_view() {
+        let commands = vec![
+            command(Primitive::Circle { radius: 1.0 }, Point::new(5.0, 5.0), 0.0),
+            command(Primitive::Circle { radius: 1.0 }, Point::new(0.0, 0.0), 0.0),
+        ];
+        let image = draw_commands(view(Point::new(5.0, 5.0), 2.0), commands);
+        // the view center is in the middle of the image, every world unit is 2 pixels
+        assert_eq!(image.pixel(20, 20), RED);
+        assert_eq!(image.pixel(21, 20), RED);
+        assert_eq!(image.pixel(22, 20), BLACK);
+        assert_eq!(image.pixel(10, 10), RED);
+        assert_eq!(image.pixel(15, 15), BLACK);
+    }
+
+    #[test]
+    fn compound_parts_turn_with_the_entity() {
+        let mut game = Game::new();
+        let material = Material {
+            fill: Some(colors::RED),
+            ..Material::default()
+        };
+        let part = SubShape {
+            offset: Vector::new(2.0, 0.0),
+            rotation: 0.0,
+            shape: Shape::Circle(0.5),
+            material: None,
+        };
+        game.world
+            .create_entity()
+            .with(Position(Point::new(0.0, 0.0)))
+            .with(Rotation(FRAC_PI_2))
+            .with(Shape::Compound(vec![part]))
+            .with(material)
+            .build();
+        game.tick();
+
+        let mut list = game.draw_list();
+        list.commands.retain(|command| command.layer != layers::BACKGROUND);
+        let image = draw(&list, &SpriteAtlas::default());
+        let pixel = |p: Point| {
+            let p = list.view.to_screen(p);
+            image.pixel(p.x as u32, p.y as u32)
+        };
+        assert_eq!(pixel(Point::new(0.0, 2.0)), RED);
+        assert_eq!(pixel(Point::new(2.0, 0.0)), BLACK);
+        assert_eq!(pixel(Point::new(0.0, 0.0)), BLACK);
+    }
+
+    #[test]
+    fn parallax_stars() {
+        let view = View {
+            center: Point::new(123.0, -45.0),
+            zoom: 0.5,
+            size: Vector::new(64.0, 48.0),
+        };
+        let mut commands = Vec::new();
+        Parallax::new().draw(&view, |position, radius, color| {
+            let mut star = command(Primitive::Circle { radius }, position, 0.0);
+            star.color = color;
+            commands.push(star);
+        });
+        assert!(!commands.is_empty());
+        let image = draw_commands(view, commands);
+        assert_golden(&image, "parallax");
+    }
+}
@@ -0,0 +1,369 @@
+use game::camera::Camera;
+use game::components::*;
+use game::data::DataError;
+use game::parallax::Parallax;
+use game::spatial::SpatialGrid;
+use game::sprites::{Region, SpriteAtlas};
+use ron;
+use specs::*;
+use std::fs;
+use std::path::Path;
+
+pub type Color = [f32; 4];
+
+pub mod colors {
+    use super::Color;
+
+    pub const BLACK: Color = [0.0, 0.0, 0.0, 1.0];
+    pub const BLUE: Color = [0.0, 0.0, 1.0, 1.0];
+    pub const GREEN: Color = [0.0, 1.0, 0.0, 1.0];
+    pub const RED: Color = [1.0, 0.0, 0.0, 1.0];
+    pub const WHITE: Color = [1.0, 1.0, 1.0, 1.0];
+    pub const YELLOW: Color = [1.0, 1.0, 0.0, 1.0];
+    pub const TRANSPARENT: Color = [0.0, 0.0, 0.0, 0.0];
+}
+
+// draw order, lower layers are drawn first
+pub mod layers {
+    pub const BACKGROUND: i32 = -100;
+    // the default for entities
+    pub const ENTITIES: i32 = 0;
+    pub const PROJECTILES: i32 = 10;
+    pub const EFFECTS: i32 = 20;
+}
+
+// everything to draw in one frame, built by `Game::draw_list`
+// frontends only have to translate it into their own drawing calls
+#[derive(Clone, Debug, Serialize)]
+pub struct DrawList {
+    pub view: View,
+    pub background: Color,
+    // sorted by layer
+    pub commands: Vec<DrawCommand>,
+}
+
+impl DrawList {
+    // writes the list as readable RON, to compare what is drawn between runs
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DataError> {
+        let config = ron::ser::PrettyConfig::default();
+        fs::write(path, ron::ser::to_string_pretty(self, config)?)?;
+        Ok(())
+    }
+}
+
+// the camera the frame is seen through, see `Camera`
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct View {
+    pub center: Point,
+    pub zoom: f64,
+    // in pixels
+    pub size: Vector,
+}
+
+impl View {
+    // like `Camera::world_to_screen`, but where the camera was drawn
+    pub fn to_screen(&self, p: Point) -> Point {
+        Point::new(
+            (p.x - self.center.x) * self.zoom + self.size.dx / 2.0,
+            (p.y - self.center.y) * self.zoom + self.size.dy / 2.0,
+        )
+    }
+
+    // world coords of the top left and bottom right corners
+    pub fn bounds(&self) -> (Point, Point) {
+        let half_size = self.size * (0.5 / self.zoom);
+        (self.center + -half_size, self.center + half_size)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DrawCommand {
+    pub primitive: Primitive,
+    // world transform of the primitive
+    pub position: Point,
+    pub rotation: f64,
+    pub color: Color,
+    pub outline: Option<Outline>,
+    pub layer: i32,
+}
+
+// line centered on the edge of a primitive
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Outline {
+    pub color: Color,
+    // in world coords
+    pub width: f64,
+}
+
+// shapes centered on their origin, before the command's transform
+#[derive(Clone, Debug, Serialize)]
+pub enum Primitive {
+    Circle {
+        radius: f64,
+    },
+    Rectangle {
+        size: Vector,
+    },
+    // `region` of the sprite atlas stretched over `size`, tinted by the command's colour
+    Sprite {
+        name: String,
+        size: Vector,
+        region: Region,
+    },
+    // filled by drawing `triangles`, indices into `points`, outlined along `points`
+    Polygon {
+        points: Vec<Point>,
+        triangles: Vec<[usize; 3]>,
+    },
+    // line segments between `points`, never outlined
+    Polyline {
+        points: Vec<Point>,
+        width: f64,
+    },
+}
+
+pub fn build_draw_list(world: &World) -> DrawList {
+    let camera = world.read_resource::<Camera>();
+    let alpha = world.read_resource::<Interpolation>().alpha;
+    let view = View {
+        center: camera.interpolated_center(alpha) + camera.shake_offset(),
+        zoom: camera.get_zoom(),
+        size: camera.viewport(),
+    };
+
+    let mut commands = Vec::new();
+    Parallax::new().draw(&view, |position, radius, color| {
+        commands.push(DrawCommand {
+            primitive: Primitive::Circle { radius },
+            position,
+            rotation: 0.0,
+            color,
+            outline: None,
+            layer: layers::BACKGROUND,
+        })
+    });
+
+    let pos_storage = world.read_storage::<Position>();
+    let rot_storage = world.read_storage::<Rotation>();
+    let previous_storage = world.read_storage::<PreviousTransform>();
+    let shape_storage = world.read_storage::<Shape>();
+    let material_storage = world.read_storage::<Material>();
+    let default_material = Material::default();
+    let grid = world.read_resource::<SpatialGrid>();
+    let mut sprites = world.write_resource::<SpriteAtlas>();
+
+    // only draw what's on screen
+    let (min, max) = view.bounds();
+    for entity in grid.intersecting_aabb(min, max) {
+        if let (Some(pos), Some(rot), Some(shape)) = (
+            pos_storage.get(entity),
+            rot_storage.get(entity),
+            shape_storage.get(entity),
+        ) {
+            // draw in between the last two ticks
+            let (pos, rot) = match previous_storage.get(entity) {
+                Some(previous) => previous.interpolate(pos, rot, alpha),
+                None => (pos.0, rot.0),
+            };
+            let material = material_storage.get(entity).unwrap_or(&default_material);
+            push_shape(&mut commands, &mut sprites, shape, pos, rot, material);
+        }
+    }
+
+    // stable, so commands in the same layer keep their order
+    commands.sort_by_key(|command| command.layer);
+
+    DrawList {
+        view,
+        background: colors::BLACK,
+        commands,
+    }
+}
+
+// adds the primitives of `shape` placed at `position` and turned by `rotation`
+fn push_shape(
+    commands: &mut Vec<DrawCommand>,
+    sprites: &mut SpriteAtlas,
+    shape: &Shape,
+    position: Point,
+    rotation: f64,
+    material: &Material,
+) {
+    use self::colors::*;
+
+    let fill = |default| material.fill.unwrap_or(default);
+    let (primitive, color) = match shape {
+        Shape::Circle(radius) => (Primitive::Circle { radius: *radius }, fill(RED)),
+        Shape::Rectangle(size) => (Primitive::Rectangle { size: *size }, fill(BLUE)),
+        Shape::Sprite(name, size) => match sprites.find(name) {
+            Some(region) => (
+                Primitive::Sprite {
+                    name: name.clone(),
+                    size: *size,
+                    region,
+                },
+                fill(WHITE),
+            ),
+            // placeholder for missing sprites
+            None => (Primitive::Rectangle { size: *size }, GREEN),
+        },
+        Shape::Polygon(ref polygon) => (
+            Primitive::Polygon {
+                points: polygon.points().to_vec(),
+                triangles: polygon.triangles().to_vec(),
+            },
+            fill(YELLOW),
+        ),
+        Shape::Polyline(ref points, width) => (
+            Primitive::Polyline {
+                points: points.clone(),
+                width: *width,
+            },
+            fill(WHITE),
+        ),
+        Shape::Compound(ref subshapes) => {
+            for subshape in subshapes {
+                push_shape(
+                    commands,
+                    sprites,
+                    &subshape.shape,
+                    position + subshape.offset.rotated(rotation),
+                    rotation + subshape.rotation,
+                    subshape.material.as_ref().unwrap_or(material),
+                );
+            }
+            return;
+        }
+    };
+    commands.push(DrawCommand {
+        primitive,
+        position,
+        rotation,
+        color: with_alpha(color, material.alpha),
+        outline: material.outline.map(|color| Outline {
+            color: with_alpha(color, material.alpha),
+            width: material.outline_width,
+        }),
+        layer: material.layer,
+    });
+}
+
+fn with_alpha(mut color: Color, alpha: f32) -> Color {
+    color[3] *= alpha;
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::Game;
+    use specs::{Builder, Entity};
+
+    fn spawn(game: &mut Game, position: Point, shape: Shape, layer: i32) -> Entity {
+        game.world
+            .create_entity()
+            .with(Position(position))
+            .with(Rotation::default())
+            .with(shape)
+            .with(Material {
+                layer,
+                ..Material::default()
+            }).build()
+    }
+
+    // commands of the entities, leaving out the background
+    fn entity_commands(list: &DrawList) -> Vec<&DrawCommand> {
+        list.commands
+            .iter()
+            .filter(|command| command.layer != layers::BACKGROUND)
+            .collect()
+    }
+
+    fn near(a: Point, b: Point) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn commands_are_sorted_by_layer() {
+        let mut game = Game::new();
+        let circle = Shape::Circle(0.5);
+        spawn(&mut game, Point::new(0.0, 0.0), circle.clone(), layers::EFFECTS);
+        spawn(&mut game, Point::new(1.0, 0.0), circle.clone(), layers::ENTITIES);
+        spawn(&mut game, Point::new(2.0, 0.0), circle.clone(), layers::PROJECTILES);
+        spawn(&mut game, Point::new(3.0, 0.0), circle.clone(), layers::ENTITIES);
+        spawn(&mut game, Point::new(4.0, 0.0), circle, -1);
+        game.tick();
+
+        let list = game.draw_list();
+        let layers: Vec<_> = list.commands.iter().map(|command| command.layer).collect();
+        let mut sorted = layers.clone();
+        sorted.sort();
+        assert_eq!(layers, sorted);
+        assert_eq!(layers[0], layers::BACKGROUND);
+
+        // the same layer keeps the order the entities come in
+        let xs: Vec<_> = entity_commands(&list).iter().map(|c| c.position.x).collect();
+        assert_eq!(xs, vec![4.0, 1.0, 3.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn entities_off_screen_are_culled() {
+        let mut game = Game::new();
+        game.tick();
+        let (_, max) = game.draw_list().view.bounds();
+        let circle = Shape::Circle(1.0);
+        let layer = layers::ENTITIES;
+        spawn(&mut game, Point::new(0.0, 0.0), circle.clone(), layer);
+        // only its edge is on screen
+        spawn(&mut game, Point::new(max.x + 0.5, 0.0), circle.clone(), layer);
+        spawn(&mut game, Point::new(max.x + 2.0, 0.0), circle.clone(), layer);
+        spawn(&mut game, Point::new(0.0, max.y + 2.0), circle.clone(), layer);
+        spawn(&mut game, Point::new(-1000.0, -1000.0), circle, layer);
+        // parts of compounds stick out further than their entity's origin
+        let arm = Shape::Compound(vec![SubShape {
+            offset: Vector::new(-3.0, 0.0),
+            rotation: 0.0,
+            shape: Shape::Circle(1.0),
+            material: None,
+        }]);
+        spawn(&mut game, Point::new(max.x + 3.5, 0.0), arm, layer);
+        game.tick();
+
+        let list = game.draw_list();
+        let xs: Vec<_> = entity_commands(&list).iter().map(|c| c.position.x).collect();
+        assert_eq!(xs, vec![0.0, max.x + 0.5, max.x + 0.5]);
+    }
+
+    #[test]
+    fn positions_are_interpolated_between_ticks() {
+        let mut game = Game::new();
+        let entity = spawn(&mut game, Point::new(0.0, 5.0), Shape::Circle(1.0), layers::ENTITIES);
+        game.world
+            .write_storage()
+            .insert(entity, Velocity::new(12.0, 0.0))
+            .unwrap();
+        game.world
+            .write_storage()
+            .insert(entity, AngularVelocity::new(3.0))
+            .unwrap();
+        game.tick();
+        game.tick();
+
+        // one tick moves it by 0.2 and turns it by 0.05
+        let drawn = |game: &mut Game, alpha| {
+            game.world.write_resource::<Interpolation>().alpha = alpha;
+            let list = game.draw_list();
+            let command = entity_commands(&list)[0];
+            (command.position, command.rotation)
+        };
+        let (position, rotation) = drawn(&mut game, 0.0);
+        assert!(near(position, Point::new(0.2, 5.0)), "{:?}", position);
+        assert!((rotation - 0.05).abs() < 1e-9);
+        let (position, rotation) = drawn(&mut game, 0.25);
+        assert!(near(position, Point::new(0.25, 5.0)), "{:?}", position);
+        assert!((rotation - 0.0625).abs() < 1e-9);
+        let (position, rotation) = drawn(&mut game, 1.0);
+        assert!(near(position, Point::new(0.4, 5.0)), "{:?}", position);
+        assert!((rotation - 0.1).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,177 @@
+use game::components::*;
+use game::data::{self, DataError};
+use ron;
+use specs::*;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hasher;
+use std::path::Path;
+
+// everything needed to play a run again, recorded by `Game::start_recording`
+// replays start from `Game::start`, so recordings have to start right after it
+#[derive(Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    pub tick_rate: f64,
+
+    // input of every tick, with the number of ticks in a row it didn't change for
+    inputs: Vec<(u32, Input)>,
+
+    // `world_hash` after the last tick
+    pub hash: Option<u64>,
+}
+
+impl Recording {
+    pub fn new(seed: u64, tick_rate: f64) -> Self {
+        Recording {
+            seed,
+            tick_rate,
+            inputs: Vec::new(),
+            hash: None,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DataError> {
+        data::load(path)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DataError> {
+        fs::write(path, ron::ser::to_string(self)?)?;
+        Ok(())
+    }
+
+    // adds the input of the next tick
+    pub fn push(&mut self, input: &Input) {
+        if let Some((count, last)) = self.inputs.last_mut() {
+            if last == input {
+                *count += 1;
+                return;
+            }
+        }
+        self.inputs.push((1, input.clone()));
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.inputs.iter().map(|&(count, _)| count).sum()
+    }
+
+    // input of every tick
+    pub fn inputs(&self) -> impl Iterator<Item = &Input> {
+        self.inputs
+            .iter()
+            .flat_map(|(count, input)| (0..*count).map(move |_| input))
+    }
+}
+
+// hash of every component of every entity, equal worlds have equal hashes
+pub fn world_hash(world: &World) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u64(world.read_resource::<Clock>().time.to_bits());
+    hash_storage::<Position>(world, &mut hasher);
+    hash_storage::<Rotation>(world, &mut hasher);
+    hash_storage::<Velocity>(world, &mut hasher);
+    hash_storage::<Acceleration>(world, &mut hasher);
+    hash_storage::<AngularVelocity>(world, &mut hasher);
+    hash_storage::<FlightModel>(world, &mut hasher);
+    hash_storage::<RigidBody>(world, &mut hasher);
+    hash_storage::<Shape>(world, &mut hasher);
+    hash_storage::<Weapon>(world, &mut hasher);
+    hash_storage::<Projectile>(world, &mut hasher);
+    hash_storage::<Lifetime>(world, &mut hasher);
+    hash_storage::<Health>(world, &mut hasher);
+    hash_storage::<Damage>(world, &mut hasher);
+    hash_storage::<AiBehaviour>(world, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_storage<T: Component + Debug>(world: &World, hasher: &mut DefaultHasher) {
+    // `Debug` covers every field and prints floats exactly
+    for (entity, component) in (&*world.entities(), &world.read_storage::<T>()).join() {
+        hasher.write_u32(entity.id());
+        hasher.write(format!("{:?}", component).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Game, Timestep};
+
+    // long enough for the first wave to come in
+    const TICKS: u32 = 400;
+
+    // flies up and then down, firing in bursts and aiming somewhere else halfway
+    fn script(tick: u32) -> Input {
+        Input {
+            up: tick < 200,
+            down: tick >= 250,
+            fire: tick % 90 < 60,
+            mouse_position: if tick < 150 { [500.0, 100.0] } else { [300.0, 500.0] },
+            viewport_size: [800.0, 600.0],
+            ..Input::default()
+        }
+    }
+
+    fn record<F: Fn(u32) -> Input>(seed: u64, script: F) -> Recording {
+        let mut game = Game::new();
+        game.set_seed(seed);
+        game.start();
+        game.start_recording();
+        // real time that doesn't line up with ticks, so some updates run none and some two
+        let dt = Timestep::default().delta() * 1.3;
+        let mut updates = 0;
+        while game.recording.as_ref().unwrap().ticks() < TICKS {
+            *game.input_mut() = script(updates);
+            game.update(dt);
+            updates += 1;
+        }
+        game.stop_recording().unwrap()
+    }
+
+    fn replay(recording: &Recording) -> u64 {
+        let mut game = Game::new();
+        game.set_seed(recording.seed);
+        game.set_timestep(Timestep {
+            tick_rate: recording.tick_rate,
+            ..Timestep::default()
+        });
+        game.start();
+        for input in recording.inputs() {
+            *game.input_mut() = input.clone();
+            game.tick();
+        }
+        world_hash(&game.world)
+    }
+
+    #[test]
+    fn replays_end_in_the_recorded_world() {
+        let recording = record(7, script);
+        assert_eq!(recording.ticks(), TICKS);
+        // held inputs are only stored once
+        assert!(recording.inputs.len() < TICKS as usize / 2);
+        assert_eq!(Some(replay(&recording)), recording.hash);
+
+        // and the same after writing it out
+        let source = ron::ser::to_string(&recording).unwrap();
+        let loaded: Recording = ron::de::from_str(&source).unwrap();
+        assert_eq!(loaded.ticks(), TICKS);
+        assert!(loaded.inputs().eq(recording.inputs()));
+        assert_eq!(Some(replay(&loaded)), recording.hash);
+    }
+
+    #[test]
+    fn other_seeds_and_inputs_end_elsewhere() {
+        let hash = record(7, script).hash;
+        assert_ne!(record(8, script).hash, hash);
+        let fire_later = |tick| Input {
+            fire: tick % 90 >= 30,
+            ..script(tick)
+        };
+        assert_ne!(record(7, fire_later).hash, hash);
+
+        let mut recording = record(7, script);
+        recording.seed = 8;
+        assert_ne!(Some(replay(&recording)), hash);
+    }
+}
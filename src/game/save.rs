@@ -0,0 +1,409 @@
+use game::camera::Camera;
+use game::components::*;
+use game::data::DataError;
+use game::waves::{WaveDirector, WaveDirectorData};
+use ron;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use specs::error::NoError;
+use specs::saveload::{
+    DeserializeComponents, FromDeserialize, IntoSerialize, Marker, MarkerAllocator,
+    SerializeComponents, U64Marker, U64MarkerAllocator,
+};
+use specs::world::EntitiesRes;
+use specs::*;
+use std::fmt;
+
+// every registered component, in the order they're stored in a save
+type ReadComponents<'a> = (
+    ReadStorage<'a, Position>,
+    ReadStorage<'a, Rotation>,
+    ReadStorage<'a, Velocity>,
+    ReadStorage<'a, Acceleration>,
+    ReadStorage<'a, AngularVelocity>,
+    ReadStorage<'a, FlightModel>,
+    ReadStorage<'a, RigidBody>,
+    ReadStorage<'a, Shape>,
+    ReadStorage<'a, Material>,
+    ReadStorage<'a, Weapon>,
+    ReadStorage<'a, Projectile>,
+    ReadStorage<'a, Lifetime>,
+    ReadStorage<'a, Health>,
+    ReadStorage<'a, Damage>,
+    ReadStorage<'a, AiBehaviour>,
+);
+
+type WriteComponents<'a> = (
+    WriteStorage<'a, Position>,
+    WriteStorage<'a, Rotation>,
+    WriteStorage<'a, Velocity>,
+    WriteStorage<'a, Acceleration>,
+    WriteStorage<'a, AngularVelocity>,
+    WriteStorage<'a, FlightModel>,
+    WriteStorage<'a, RigidBody>,
+    WriteStorage<'a, Shape>,
+    WriteStorage<'a, Material>,
+    WriteStorage<'a, Weapon>,
+    WriteStorage<'a, Projectile>,
+    WriteStorage<'a, Lifetime>,
+    WriteStorage<'a, Health>,
+    WriteStorage<'a, Damage>,
+    WriteStorage<'a, AiBehaviour>,
+);
+
+#[derive(Serialize, Deserialize)]
+struct SavedResources {
+    clock: Clock,
+    camera: Camera,
+    random: Random,
+    player: Option<U64Marker>,
+    waves: WaveDirectorData<U64Marker>,
+}
+
+// serializes every entity and the game resources into a RON string
+pub fn save(world: &World) -> Result<String, DataError> {
+    // make sure every entity has a marker, they replace entity references in the save
+    {
+        let entities = world.entities();
+        let mut markers = world.write_storage::<U64Marker>();
+        let mut allocator = world.write_resource::<U64MarkerAllocator>();
+        for entity in entities.join() {
+            allocator.mark(entity, &mut markers);
+        }
+    }
+
+    let markers = world.read_storage::<U64Marker>();
+    let ids = |entity| markers.get(entity).cloned();
+    let resources = SavedResources {
+        clock: world.read_resource::<Clock>().clone(),
+        camera: world.read_resource::<Camera>().clone(),
+        random: world.read_resource::<Random>().clone(),
+        player: match *world.read_resource::<Option<Player>>() {
+            Some(Player(entity)) => ids(entity),
+            None => None,
+        },
+        waves: world.read_resource::<WaveDirector>().to_data(ids),
+    };
+
+    let save = Save {
+        resources: &resources,
+        entities: SavedEntities {
+            entities: &world.entities(),
+            markers: &markers,
+            components: &world.system_data(),
+        },
+    };
+    Ok(ron::ser::to_string_pretty(&save, Default::default())?)
+}
+
+// replaces every entity and the game resources with the ones from a RON string
+pub fn load(world: &mut World, source: &str) -> Result<(), DataError> {
+    // read it into a scratch world first, so a broken save leaves the running game alone
+    read(&mut create_world(), source)?;
+    read(world, source)
+}
+
+// like `load`, but leaves `world` half loaded when the save is broken
+fn read(world: &mut World, source: &str) -> Result<(), DataError> {
+    world.maintain();
+    // freed ids come back last freed first, so free them from the top to hand them out again
+    // in the saved order, and the loaded world runs its entities in the same order
+    // deleting bumps their generations, so old handles don't find the loaded entities
+    let mut entities: Vec<_> = world.entities().join().collect();
+    entities.reverse();
+    world
+        .delete_entities(&entities)
+        .expect("entities were just collected");
+    world.add_resource(U64MarkerAllocator::new());
+
+    let resources = {
+        let mut deserializer = ron::de::Deserializer::from_str(source)?;
+        let seed = SaveSeed {
+            entities: &world.entities(),
+            markers: &mut world.write_storage(),
+            allocator: &mut world.write_resource(),
+            components: &mut world.system_data(),
+        };
+        let resources = seed.deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        resources
+    };
+
+    let allocator = world.read_resource::<U64MarkerAllocator>();
+    let ids = |marker: U64Marker| allocator.retrieve_entity_internal(marker.id());
+    *world.write_resource() = resources.clock;
+    *world.write_resource() = resources.camera;
+    *world.write_resource() = resources.random;
+    *world.write_resource() = resources.player.and_then(ids).map(Player);
+    *world.write_resource() = WaveDirector::from_data(resources.waves, ids);
+    Ok(())
+}
+
+// `Projectile` with entities replaced by save markers
+#[derive(Serialize, Deserialize)]
+pub struct ProjectileData<M> {
+    owner: Option<M>,
+}
+
+impl<M: Marker> IntoSerialize<M> for Projectile {
+    type Data = ProjectileData<M>;
+    type Error = NoError;
+
+    fn into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(ProjectileData {
+            owner: self.owner.and_then(&mut ids),
+        })
+    }
+}
+
+impl<M: Marker> FromDeserialize<M> for Projectile {
+    type Data = ProjectileData<M>;
+    type Error = NoError;
+
+    fn from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Projectile {
+            owner: data.owner.and_then(ids),
+        })
+    }
+}
+
+// `Health` with entities replaced by save markers
+#[derive(Serialize, Deserialize)]
+pub struct HealthData<M> {
+    hit_points: f64,
+    shield: f64,
+    last_attacker: Option<M>,
+}
+
+impl<M: Marker> IntoSerialize<M> for Health {
+    type Data = HealthData<M>;
+    type Error = NoError;
+
+    fn into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(HealthData {
+            hit_points: self.hit_points,
+            shield: self.shield,
+            last_attacker: self.last_attacker.and_then(&mut ids),
+        })
+    }
+}
+
+impl<M: Marker> FromDeserialize<M> for Health {
+    type Data = HealthData<M>;
+    type Error = NoError;
+
+    fn from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Health {
+            hit_points: data.hit_points,
+            shield: data.shield,
+            last_attacker: data.last_attacker.and_then(ids),
+        })
+    }
+}
+
+// the layout of a save file
+struct Save<'s, 'a: 's> {
+    resources: &'s SavedResources,
+    entities: SavedEntities<'s, 'a>,
+}
+
+impl<'s, 'a> Serialize for Save<'s, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut save = serializer.serialize_struct("Save", 2)?;
+        save.serialize_field("resources", self.resources)?;
+        save.serialize_field("entities", &self.entities)?;
+        save.end()
+    }
+}
+
+struct SavedEntities<'s, 'a: 's> {
+    entities: &'s EntitiesRes,
+    markers: &'s ReadStorage<'a, U64Marker>,
+    components: &'s ReadComponents<'a>,
+}
+
+impl<'s, 'a> Serialize for SavedEntities<'s, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializeComponents::<NoError, U64Marker>::serialize(
+            self.components,
+            self.entities,
+            self.markers,
+            serializer,
+        )
+    }
+}
+
+// loads entities straight into the world while reading a save, returns the resources
+struct SaveSeed<'s, 'a: 's> {
+    entities: &'s EntitiesRes,
+    markers: &'s mut WriteStorage<'a, U64Marker>,
+    allocator: &'s mut U64MarkerAllocator,
+    components: &'s mut WriteComponents<'a>,
+}
+
+impl<'de, 's, 'a> DeserializeSeed<'de> for SaveSeed<'s, 'a> {
+    type Value = SavedResources;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("Save", &["resources", "entities"], self)
+    }
+}
+
+impl<'de, 's, 'a> Visitor<'de> for SaveSeed<'s, 'a> {
+    type Value = SavedResources;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a save with resources and entities")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut resources = None;
+        let mut entities_loaded = false;
+        while let Some(field) = map.next_key()? {
+            match field {
+                SaveField::Resources => resources = Some(map.next_value()?),
+                SaveField::Entities => {
+                    map.next_value_seed(EntitiesSeed {
+                        entities: self.entities,
+                        markers: &mut *self.markers,
+                        allocator: &mut *self.allocator,
+                        components: &mut *self.components,
+                    })?;
+                    entities_loaded = true;
+                }
+            }
+        }
+        if !entities_loaded {
+            return Err(de::Error::missing_field("entities"));
+        }
+        resources.ok_or_else(|| de::Error::missing_field("resources"))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum SaveField {
+    Resources,
+    Entities,
+}
+
+struct EntitiesSeed<'s, 'a: 's> {
+    entities: &'s EntitiesRes,
+    markers: &'s mut WriteStorage<'a, U64Marker>,
+    allocator: &'s mut U64MarkerAllocator,
+    components: &'s mut WriteComponents<'a>,
+}
+
+impl<'de, 's, 'a> DeserializeSeed<'de> for EntitiesSeed<'s, 'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        DeserializeComponents::<NoError, U64Marker>::deserialize(
+            self.components,
+            self.entities,
+            self.markers,
+            self.allocator,
+            deserializer,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::components::Point;
+    use game::replay::world_hash;
+    use game::Game;
+    use shrev::EventChannel;
+    use std::{env, fs};
+
+    fn running_game() -> Game<'static, 'static> {
+        let mut game = Game::new();
+        game.create_player();
+        // far enough that nothing gets hit and deleted, deleted entities leave gaps in the ids
+        assert!(game.spawn_prefab("seeker", Point::new(-60.0, 40.0)).is_some());
+        assert!(game.spawn_prefab("strafer", Point::new(-50.0, -60.0)).is_some());
+        game.input_mut().fire = true;
+        for _ in 0..30 {
+            game.tick();
+        }
+        game
+    }
+
+    #[test]
+    fn round_trip_keeps_the_world() {
+        let game = running_game();
+        let source = save(&game.world).unwrap();
+        let mut loaded = Game::new();
+        load(&mut loaded.world, &source).unwrap();
+        assert_eq!(world_hash(&loaded.world), world_hash(&game.world));
+        assert_eq!(save(&loaded.world).unwrap(), source);
+    }
+
+    #[test]
+    fn loading_over_a_running_game_drops_old_entities() {
+        let mut game = running_game();
+        let source = save(&game.world).unwrap();
+        let old: Vec<_> = game.world.entities().join().collect();
+        // an event still waiting to be read, for an entity of the old world
+        game.world
+            .write_resource::<EventChannel<GameEvent>>()
+            .single_write(GameEvent::Spawned { entity: old[0] });
+
+        let path = env::temp_dir().join("deasil_save_test.ron");
+        fs::write(&path, &source).unwrap();
+        game.load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // same ids in the same order, but none of the old handles find anything
+        let loaded: Vec<_> = game.world.entities().join().collect();
+        let ids = |entities: &[Entity]| entities.iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids(&loaded), ids(&old));
+        assert!(old.iter().all(|&entity| !game.world.is_alive(entity)));
+        assert!(game.events().is_empty());
+        assert!(game.world.read_resource::<Collisions>().0.is_empty());
+        assert_eq!(save(&game.world).unwrap(), source);
+
+        // and it goes on like a game loaded into a new world
+        let mut fresh = Game::new();
+        load(&mut fresh.world, &source).unwrap();
+        // input isn't saved
+        fresh.input_mut().fire = true;
+        for _ in 0..30 {
+            game.tick();
+            fresh.tick();
+            assert_eq!(game.events().len(), fresh.events().len());
+        }
+        assert_eq!(save(&game.world).unwrap(), save(&fresh.world).unwrap());
+    }
+
+    #[test]
+    fn broken_save_leaves_the_world_alone() {
+        let mut game = running_game();
+        let source = save(&game.world).unwrap();
+        let hash = world_hash(&game.world);
+        let entities = game.world.entities().join().count();
+
+        assert!(load(&mut game.world, &source[..source.len() / 2]).is_err());
+        assert!(load(&mut game.world, "not a save").is_err());
+        assert_eq!(world_hash(&game.world), hash);
+        assert_eq!(game.world.entities().join().count(), entities);
+        let player = match *game.world.read_resource::<Option<Player>>() {
+            Some(Player(entity)) => entity,
+            None => panic!("the player is gone"),
+        };
+        assert!(game.world.is_alive(player));
+    }
+}
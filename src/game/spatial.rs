@@ -0,0 +1,228 @@
+use game::components::*;
+use specs::Entity;
+use std::collections::HashMap;
+
+// world coords, a bit bigger than a ship
+const DEFAULT_CELL_SIZE: f64 = 4.0;
+
+struct Entry {
+    entity: Entity,
+    center: Point,
+    radius: f64,
+    // inclusive range of cells covered by the bounding circle
+    min_cell: (i64, i64),
+    max_cell: (i64, i64),
+}
+
+// uniform grid of bounding circles, rebuilt every update by `SpatialIndexSys`
+pub struct SpatialGrid {
+    cell_size: f64,
+    entries: Vec<Entry>,
+    // indices into `entries`
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        SpatialGrid::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> Self {
+        SpatialGrid {
+            cell_size,
+            entries: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, center: Point, radius: f64) {
+        let index = self.entries.len();
+        let (min_cell, max_cell) = self.cell_range(
+            Point::new(center.x - radius, center.y - radius),
+            Point::new(center.x + radius, center.y + radius),
+        );
+        for x in min_cell.0..=max_cell.0 {
+            for y in min_cell.1..=max_cell.1 {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+        self.entries.push(Entry {
+            entity,
+            center,
+            radius,
+            min_cell,
+            max_cell,
+        });
+    }
+
+    // entities whose bounding circle overlaps the given circle
+    pub fn within_radius(&self, center: Point, radius: f64) -> Vec<Entity> {
+        let min = Point::new(center.x - radius, center.y - radius);
+        let max = Point::new(center.x + radius, center.y + radius);
+        self.query(min, max, |entry| {
+            (entry.center - center).length() < entry.radius + radius
+        })
+    }
+
+    // entities whose bounding circle overlaps the given axis aligned box
+    pub fn intersecting_aabb(&self, min: Point, max: Point) -> Vec<Entity> {
+        self.query(min, max, |entry| {
+            let closest = Point::new(
+                entry.center.x.clamp(min.x, max.x),
+                entry.center.y.clamp(min.y, max.y),
+            );
+            (closest - entry.center).length() < entry.radius
+        })
+    }
+
+    // pairs of entities sharing at least one cell, each pair reported once
+    pub fn potential_pairs(&self) -> Vec<(Entity, Entity)> {
+        let mut pairs = Vec::new();
+        for (i, a) in self.entries.iter().enumerate() {
+            let mut candidates = self.indices_in_cells(a.min_cell, a.max_cell);
+            candidates.retain(|&j| j > i);
+            for j in candidates {
+                let b = &self.entries[j];
+                if (b.center - a.center).length() < a.radius + b.radius {
+                    pairs.push((a.entity, b.entity));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn query<F>(&self, min: Point, max: Point, filter: F) -> Vec<Entity>
+    where
+        F: Fn(&Entry) -> bool,
+    {
+        let (min_cell, max_cell) = self.cell_range(min, max);
+        self.indices_in_cells(min_cell, max_cell)
+            .into_iter()
+            .map(|i| &self.entries[i])
+            .filter(|entry| filter(entry))
+            .map(|entry| entry.entity)
+            .collect()
+    }
+
+    // sorted and without duplicates, so results follow insertion order
+    fn indices_in_cells(&self, min_cell: (i64, i64), max_cell: (i64, i64)) -> Vec<usize> {
+        let mut indices = Vec::new();
+        if (max_cell.0 - min_cell.0 + 1) * (max_cell.1 - min_cell.1 + 1) > self.cells.len() as i64
+        {
+            // the area is bigger than the occupied part of the grid, visit occupied cells only
+            for (&(x, y), cell) in &self.cells {
+                if x >= min_cell.0 && x <= max_cell.0 && y >= min_cell.1 && y <= max_cell.1 {
+                    indices.extend(cell);
+                }
+            }
+        } else {
+            for x in min_cell.0..=max_cell.0 {
+                for y in min_cell.1..=max_cell.1 {
+                    if let Some(cell) = self.cells.get(&(x, y)) {
+                        indices.extend(cell);
+                    }
+                }
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    fn cell_range(&self, min: Point, max: Point) -> ((i64, i64), (i64, i64)) {
+        (self.cell(min), self.cell(max))
+    }
+
+    fn cell(&self, p: Point) -> (i64, i64) {
+        (
+            (p.x / self.cell_size).floor() as i64,
+            (p.y / self.cell_size).floor() as i64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, World};
+
+    // entities with the given bounding circles, inserted in order
+    fn grid(circles: &[(f64, f64, f64)]) -> (SpatialGrid, Vec<Entity>) {
+        let mut world = World::new();
+        let mut grid = SpatialGrid::default();
+        let entities = circles
+            .iter()
+            .map(|&(x, y, radius)| {
+                let entity = world.create_entity().build();
+                grid.insert(entity, Point::new(x, y), radius);
+                entity
+            }).collect();
+        (grid, entities)
+    }
+
+    #[test]
+    fn radius_queries() {
+        let (grid, e) = grid(&[
+            (0.0, 0.0, 1.0),
+            (-5.0, -5.0, 1.0),
+            (6.0, 0.0, 0.5),
+            (30.0, 0.0, 20.0),
+        ]);
+        assert_eq!(grid.within_radius(Point::new(1.5, 0.0), 1.0), vec![e[0]]);
+        assert_eq!(grid.within_radius(Point::new(-4.0, -4.0), 1.0), vec![e[1]]);
+        // touching isn't overlapping
+        assert_eq!(grid.within_radius(Point::new(4.5, 0.0), 1.0), vec![]);
+        assert_eq!(grid.within_radius(Point::new(3.0, 0.0), 3.2), vec![e[0], e[2]]);
+        // big entities are found from any cell they cover
+        assert_eq!(grid.within_radius(Point::new(12.0, 0.0), 0.1), vec![e[3]]);
+        assert_eq!(grid.within_radius(Point::new(0.0, 0.0), 100.0), e);
+    }
+
+    #[test]
+    fn box_queries() {
+        let (grid, e) = grid(&[(0.0, 0.0, 1.0), (-5.0, -5.0, 1.0), (6.0, 0.0, 0.5)]);
+        let query = |min: (f64, f64), max: (f64, f64)| {
+            grid.intersecting_aabb(Point::new(min.0, min.1), Point::new(max.0, max.1))
+        };
+        assert_eq!(query((-6.0, -6.0), (0.0, 0.0)), vec![e[0], e[1]]);
+        assert_eq!(query((0.5, 0.2), (7.0, 7.0)), vec![e[0], e[2]]);
+        // the box corner is outside the circle, even though the circle's box overlaps it
+        assert_eq!(query((0.8, 0.8), (2.0, 2.0)), vec![]);
+        assert_eq!(query((-100.0, -100.0), (100.0, 100.0)), e);
+    }
+
+    #[test]
+    fn overlapping_pairs_are_reported_once() {
+        let (mut grid, e) = grid(&[
+            (0.0, 0.0, 1.0),
+            (1.5, 0.0, 1.0),
+            (3.9, 3.9, 0.5),
+            (4.1, 4.1, 0.5),
+            (-20.0, 0.0, 1.0),
+            (0.0, 0.0, 10.0),
+        ]);
+        let mut pairs = grid.potential_pairs();
+        pairs.sort();
+        let mut expected = vec![
+            (e[0], e[1]),
+            (e[2], e[3]),
+            (e[0], e[5]),
+            (e[1], e[5]),
+            (e[2], e[5]),
+            (e[3], e[5]),
+        ];
+        expected.sort();
+        assert_eq!(pairs, expected);
+
+        grid.clear();
+        assert!(grid.potential_pairs().is_empty());
+        assert!(grid.within_radius(Point::new(0.0, 0.0), 100.0).is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+use game::data::DataError;
+use game::raster::Image;
+use game::render::colors;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+// part of the atlas image holding one sprite, in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// every sprite image packed into one texture, so frontends only upload a single one
+// sprites are named after their file without the extension
+pub struct SpriteAtlas {
+    image: Image,
+    regions: HashMap<String, Region>,
+
+    // sprites asked for but missing, each is only reported once
+    missing: HashSet<String>,
+}
+
+impl Default for SpriteAtlas {
+    fn default() -> Self {
+        SpriteAtlas::pack(Vec::new())
+    }
+}
+
+impl SpriteAtlas {
+    // empty space around every sprite, so filtering doesn't bleed between them
+    const PADDING: u32 = 1;
+
+    // packs every PNG file in `dir`, files that can't be read are reported and skipped
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, DataError> {
+        let mut sprites = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new("png")) {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            match Image::load_png(&path) {
+                Ok(image) => sprites.push((name, image)),
+                Err(e) => println!("Failed to load sprite {}: {}", path.display(), e),
+            }
+        }
+        Ok(SpriteAtlas::pack(sprites))
+    }
+
+    // places sprites in rows from the tallest to the shortest
+    pub fn pack(mut sprites: Vec<(String, Image)>) -> Self {
+        // sort by name too, so the same files always make the same atlas
+        sprites.sort_by(|(a_name, a), (b_name, b)| {
+            b.height().cmp(&a.height()).then_with(|| a_name.cmp(b_name))
+        });
+
+        // roughly square, but at least as wide as the widest sprite
+        let area: u32 = sprites
+            .iter()
+            .map(|(_, image)| {
+                (image.width() + SpriteAtlas::PADDING) * (image.height() + SpriteAtlas::PADDING)
+            }).sum();
+        let widest = sprites.iter().map(|(_, image)| image.width()).max();
+        let width = ((area as f64).sqrt().ceil() as u32)
+            .next_power_of_two()
+            .max(widest.map_or(0, |w| w + SpriteAtlas::PADDING));
+
+        let mut regions = HashMap::new();
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        for (name, image) in &sprites {
+            if x + image.width() > width {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            let region = Region {
+                x,
+                y,
+                width: image.width(),
+                height: image.height(),
+            };
+            regions.insert(name.clone(), region);
+            x += image.width() + SpriteAtlas::PADDING;
+            row_height = row_height.max(image.height() + SpriteAtlas::PADDING);
+        }
+
+        let mut atlas = Image::new(width, y + row_height, colors::TRANSPARENT);
+        for (name, image) in &sprites {
+            let region = regions[name];
+            atlas.copy_from(image, region.x, region.y);
+        }
+        SpriteAtlas {
+            image: atlas,
+            regions,
+            missing: HashSet::new(),
+        }
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn region(&self, name: &str) -> Option<Region> {
+        self.regions.get(name).cloned()
+    }
+
+    // like `region`, but reports the first time a missing sprite is asked for
+    pub fn find(&mut self, name: &str) -> Option<Region> {
+        let region = self.region(name);
+        if region.is_none() && self.missing.insert(name.to_string()) {
+            println!("Missing sprite \"{}\", drawing a placeholder", name);
+        }
+        region
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::components::*;
+    use game::render::{layers, Primitive};
+    use game::Game;
+    use specs::Builder;
+
+    fn atlas() -> SpriteAtlas {
+        let ship = Image::new(4, 3, colors::WHITE);
+        let rock = Image::new(2, 5, colors::RED);
+        SpriteAtlas::pack(vec![("ship".to_string(), ship), ("rock".to_string(), rock)])
+    }
+
+    #[test]
+    fn missing_sprites_are_reported_once() {
+        let mut atlas = atlas();
+        let ship = atlas.find("ship").unwrap();
+        assert_eq!((ship.width, ship.height), (4, 3));
+        assert!(atlas.missing.is_empty());
+
+        for _ in 0..3 {
+            assert_eq!(atlas.find("nope"), None);
+        }
+        assert_eq!(atlas.find("other"), None);
+        let mut missing: Vec<_> = atlas.missing.iter().cloned().collect();
+        missing.sort();
+        assert_eq!(missing, vec!["nope".to_string(), "other".to_string()]);
+    }
+
+    #[test]
+    fn missing_sprites_are_drawn_as_placeholders() {
+        let mut game = Game::new();
+        game.add_resource(atlas());
+        let size = Vector::new(2.0, 1.0);
+        for &(name, x) in &[("ship", -2.0), ("nope", 2.0)] {
+            game.world
+                .create_entity()
+                .with(Position::new(x, 0.0))
+                .with(Rotation::default())
+                .with(Shape::Sprite(name.to_string(), size))
+                .build();
+        }
+        game.tick();
+
+        for _ in 0..2 {
+            let list = game.draw_list();
+            // leaves out the background
+            let mut commands: Vec<_> = list
+                .commands
+                .iter()
+                .filter(|command| command.layer == layers::ENTITIES)
+                .collect();
+            commands.sort_by(|a, b| a.position.x.partial_cmp(&b.position.x).unwrap());
+            assert_eq!(commands.len(), 2);
+            match commands[0].primitive {
+                Primitive::Sprite { ref name, region, .. } => {
+                    assert_eq!(name, "ship");
+                    assert_eq!(Some(region), game.sprites().region("ship"));
+                }
+                ref primitive => panic!("ship drawn as {:?}", primitive),
+            }
+            match commands[1].primitive {
+                Primitive::Rectangle { size: drawn } => assert_eq!(drawn.dx, size.dx),
+                ref primitive => panic!("placeholder drawn as {:?}", primitive),
+            }
+            assert_eq!(commands[1].color, colors::GREEN);
+        }
+        assert_eq!(game.sprites().missing.len(), 1);
+    }
+}
@@ -1,18 +1,58 @@
 use consts;
 use game;
+use game::camera::Camera;
+use game::collision;
 use game::components::*;
+use game::physics::{self, Body};
+use game::prefabs::{self, Prefabs};
+use game::spatial::SpatialGrid;
+use game::waves::{self, WaveDirector};
+use shrev::EventChannel;
 use specs::*;
-use std::f64::consts::FRAC_PI_2;
+use std::collections::HashMap;
+
+// remembers where everything was before this tick, for `Interpolation`
+pub struct SnapshotSys;
+impl<'a> System<'a> for SnapshotSys {
+    type SystemData = (
+        Write<'a, Camera>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Rotation>,
+        WriteStorage<'a, PreviousTransform>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (mut camera, pos_storage, rot_storage, mut previous_storage, entities): Self::SystemData,
+    ) {
+        camera.snapshot();
+        for (entity, pos, rot) in (&*entities, &pos_storage, &rot_storage).join() {
+            let previous = PreviousTransform {
+                position: pos.0,
+                rotation: rot.0,
+            };
+            if let Err(e) = previous_storage.insert(entity, previous) {
+                println!("Failed to store previous transform: {:?}", e);
+            }
+        }
+    }
+}
 
 pub struct InputSys;
 impl<'a> System<'a> for InputSys {
     type SystemData = (
         Read<'a, Input>,
         Read<'a, Option<Player>>,
+        Read<'a, Prefabs>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, FlightModel>,
         Write<'a, Camera>,
         WriteStorage<'a, Acceleration>,
         WriteStorage<'a, Rotation>,
+        WriteStorage<'a, Weapon>,
+        Write<'a, Random>,
+        Write<'a, EventChannel<GameEvent>>,
         Entities<'a>,
         Read<'a, LazyUpdate>,
     );
@@ -22,53 +62,364 @@ impl<'a> System<'a> for InputSys {
         (
             input,
             player_entity_storage,
+            prefabs,
             position_storage,
+            flight_storage,
             mut camera,
             mut acc_storage,
             mut rot_storage,
+            mut weapon_storage,
+            mut random,
+            mut events,
             entities,
             updater,
         ): Self::SystemData,
     ) {
-        if input.mouse_left {
+        if input.secondary {
             // spawn dummy entities
-            game::create_dummy_entity(updater.create_entity(&entities)).build();
+            let entity =
+                game::create_dummy_entity(updater.create_entity(&entities), &mut random).build();
+            events.single_write(GameEvent::Spawned { entity });
+        }
+        if input.spawn_enemy {
+            // spawn random enemies
+            let names = prefabs.enemy_names();
+            if !names.is_empty() {
+                let name = names[random.range(0.0, names.len() as f64) as usize];
+                if let Some(prefab) = prefabs.get(name) {
+                    let entity = prefabs::build_prefab(
+                        prefab,
+                        updater.create_entity(&entities),
+                        Point::default(),
+                    ).build();
+                    events.single_write(GameEvent::Spawned { entity });
+                }
+            }
         }
 
-        // use mouse scroll to zoom
-        camera.adjust_zoom(consts::ZOOM_FACTOR.powf(input.mouse_scroll[1]));
+        // the window may have been resized
+        let [width, height] = input.viewport_size;
+        camera.set_viewport(Vector::new(width, height));
+
+        // zoom by scrolling, or a few steps every tick while zooming is held
+        const HELD_ZOOM_STEPS: f64 = 2.0;
+        let held_zoom = input.zoom_in as i32 as f64 - input.zoom_out as i32 as f64;
+        let zoom_steps = input.mouse_scroll[1] + held_zoom * HELD_ZOOM_STEPS;
+        camera.adjust_zoom(consts::ZOOM_FACTOR.powf(zoom_steps));
 
-        const BASE_ACC: f64 = 20.0;
-        let mut player_centered_point: Option<Point> = None;
         if let Some(Player(player_entity)) = *player_entity_storage {
-            let direction = input.keyboard_direction();
-            match acc_storage.insert(
-                player_entity,
-                Acceleration::new(direction.dx * BASE_ACC, direction.dy * BASE_ACC),
-            ) {
+            // turn towards the aim stick or the cursor
+            if let Some(position) = position_storage.get(player_entity) {
+                let [x, y] = input.mouse_position;
+                let aim = input
+                    .aim_direction()
+                    .unwrap_or_else(|| camera.screen_to_world(Point::new(x, y)) - position.0);
+                if aim.dx != 0.0 || aim.dy != 0.0 {
+                    let cursor_angle = f64::atan2(aim.dy, aim.dx);
+                    match rot_storage.insert(player_entity, Rotation(cursor_angle)) {
+                        Ok(_) => {}
+                        Err(e) => println!("Failed to update rotation: {:?}", e),
+                    }
+                }
+            }
+
+            // thrust after turning, newtonian ships thrust the way they face
+            let flight = flight_storage.get(player_entity).cloned().unwrap_or_default();
+            let rotation = rot_storage.get(player_entity).map_or(0.0, |rot| rot.0);
+            let thrust = flight.thrust(input.direction(), rotation);
+            match acc_storage.insert(player_entity, Acceleration(thrust)) {
                 Ok(_) => {}
                 Err(e) => println!("Failed to update acceleration: {:?}", e),
             }
 
-            let cursor_angle = f64::atan2(
-                input.mouse_position[0] - consts::WINDOW_SIZE[0] as f64 / 2.0,
-                -(input.mouse_position[1] - consts::WINDOW_SIZE[1] as f64 / 2.0),
-            ) - FRAC_PI_2;
-            match rot_storage.insert(player_entity, Rotation(cursor_angle)) {
-                Ok(_) => {}
-                Err(e) => println!("Failed to update rotation: {:?}", e),
+            if let Some(weapon) = weapon_storage.get_mut(player_entity) {
+                weapon.firing = input.fire;
             }
+        }
+    }
+}
 
-            if let Some(position) = position_storage.get(player_entity) {
-                player_centered_point = Some(position.0)
+// keeps the player in view, looking ahead to where it is going and aiming
+pub struct CameraSys;
+impl<'a> System<'a> for CameraSys {
+    type SystemData = (
+        Read<'a, Clock>,
+        Read<'a, Input>,
+        Read<'a, Option<Player>>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        Write<'a, Camera>,
+    );
+
+    fn run(
+        &mut self,
+        (clock, input, player, pos_storage, vel_storage, mut camera): Self::SystemData,
+    ) {
+        let followed = player
+            .as_ref()
+            .and_then(|&Player(entity)| pos_storage.get(entity).map(|pos| (entity, pos.0)));
+        match followed {
+            Some((entity, position)) => {
+                // a stick pushed all the way looks as far as the cursor at the edge of the screen
+                let [x, y] = input.mouse_position;
+                let aim = match input.aim_direction() {
+                    Some(aim) => {
+                        let half_view = camera.view_size() * 0.5;
+                        Vector::new(aim.dx * half_view.dx, aim.dy * half_view.dy)
+                    }
+                    None => camera.screen_to_world(Point::new(x, y)) - camera.center(),
+                };
+                let velocity = vel_storage.get(entity).map_or(Vector::default(), |vel| vel.0);
+                camera.follow(position, velocity, aim);
+            }
+            // nothing to follow, go back to the middle
+            _ => camera.follow(Point::default(), Vector::default(), Vector::default()),
+        }
+        camera.update(clock.delta);
+    }
+}
+
+pub struct WaveSys;
+impl<'a> System<'a> for WaveSys {
+    type SystemData = (
+        Read<'a, Clock>,
+        Read<'a, Camera>,
+        Read<'a, Option<Player>>,
+        Read<'a, Prefabs>,
+        ReadStorage<'a, Position>,
+        Write<'a, WaveDirector>,
+        Write<'a, Random>,
+        Write<'a, EventChannel<GameEvent>>,
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            clock,
+            camera,
+            player,
+            prefabs,
+            pos_storage,
+            mut director,
+            mut random,
+            mut events,
+            entities,
+            updater,
+        ): Self::SystemData,
+    ) {
+        // formations are centered on the player
+        let center = player
+            .as_ref()
+            .and_then(|&Player(entity)| pos_storage.get(entity))
+            .map_or(Point::default(), |pos| pos.0);
+
+        while let Some(index) = director.due_wave(clock.time, |e| entities.is_alive(e)) {
+            events.single_write(GameEvent::WaveStarted { wave: index });
+            for group in director.wave(index).groups.clone() {
+                let prefab = match prefabs.get(&group.prefab) {
+                    Some(prefab) => prefab,
+                    None => {
+                        println!("Failed to spawn wave {}: no {:?} prefab", index, group.prefab);
+                        continue;
+                    }
+                };
+                let positions = waves::formation_positions(
+                    group.formation,
+                    group.count,
+                    center,
+                    camera.view_size(),
+                    &mut random,
+                );
+                for position in positions {
+                    let entity =
+                        prefabs::build_prefab(prefab, updater.create_entity(&entities), position)
+                            .build();
+                    director.track(entity);
+                    events.single_write(GameEvent::Spawned { entity });
+                }
+            }
+        }
+    }
+}
+
+pub struct AiSys;
+impl<'a> System<'a> for AiSys {
+    type SystemData = (
+        Read<'a, Option<Player>>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        WriteStorage<'a, AiBehaviour>,
+        WriteStorage<'a, Acceleration>,
+        WriteStorage<'a, Rotation>,
+        WriteStorage<'a, Weapon>,
+        Write<'a, Random>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            player,
+            pos_storage,
+            vel_storage,
+            mut ai_storage,
+            mut acc_storage,
+            mut rot_storage,
+            mut weapon_storage,
+            mut random,
+            entities,
+        ): Self::SystemData,
+    ) {
+        // how fast the actual velocity catches up with the desired one, in s
+        const RESPONSE_TIME: f64 = 0.5;
+        // how much wandering entities change their heading every update, in radians
+        const WANDER_JITTER: f64 = 0.1;
+        // closest orbiting and strafing entities circle the player, in world system coords
+        const MIN_ORBIT_DISTANCE: f64 = 1.0;
+
+        let player = player
+            .as_ref()
+            .and_then(|&Player(entity)| pos_storage.get(entity).map(|pos| pos.0));
+
+        for (entity, pos, vel, ai) in (
+            &*entities,
+            &pos_storage,
+            vel_storage.maybe(),
+            &mut ai_storage,
+        )
+            .join()
+        {
+            let velocity = vel.map_or(Vector::default(), |v| v.0);
+
+            // the player is only a target when it's in sight
+            let target = player.filter(|&target| (target - pos.0).length() <= ai.sight_range);
+
+            let to_target = target.map(|t| t - pos.0);
+            let steering = if to_target.is_some() {
+                ai.steering
+            } else {
+                Steering::Wander
+            };
+            let to_target = to_target.unwrap_or_default();
+            let direction = to_target.normalized();
+
+            let desired_velocity = match steering {
+                Steering::Seek => direction * ai.max_speed,
+                Steering::Flee => -direction * ai.max_speed,
+                Steering::Orbit { distance } | Steering::Strafe { distance } => {
+                    // go around and correct the distance on the way, a distance of 0 would
+                    // divide by 0 and there's nothing to go around that close anyway
+                    let distance = distance.max(MIN_ORBIT_DISTANCE);
+                    let correction = (to_target.length() - distance) / distance;
+                    (direction.perpendicular() + direction * correction).normalized() * ai.max_speed
+                }
+                Steering::Wander => {
+                    ai.wander_angle += random.range(-WANDER_JITTER, WANDER_JITTER);
+                    Vector::new(ai.max_speed / 2.0, 0.0).rotated(ai.wander_angle)
+                }
+            };
+
+            let mut acceleration = (desired_velocity - velocity) * (1.0 / RESPONSE_TIME);
+            if acceleration.length() > ai.max_acceleration {
+                acceleration = acceleration.normalized() * ai.max_acceleration;
+            }
+            if let Err(e) = acc_storage.insert(entity, Acceleration(acceleration)) {
+                println!("Failed to update acceleration: {:?}", e);
+            }
+
+            // strafing entities keep their guns on the target
+            let strafing = matches!(steering, Steering::Strafe { .. });
+            let facing = if strafing {
+                direction
+            } else {
+                desired_velocity
+            };
+            if facing.length() > 0.0 {
+                if let Err(e) = rot_storage.insert(entity, Rotation(facing.angle())) {
+                    println!("Failed to update rotation: {:?}", e);
+                }
+            }
+            if let Some(weapon) = weapon_storage.get_mut(entity) {
+                weapon.firing = strafing;
             }
         }
+    }
+}
 
-        // propagate mouse position to camera
-        camera.center_at(match player_centered_point {
-            Some(point) => point,
-            None => Point::default(),
-        });
+pub struct WeaponSys;
+impl<'a> System<'a> for WeaponSys {
+    type SystemData = (
+        Read<'a, Clock>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Velocity>,
+        WriteStorage<'a, Weapon>,
+        Write<'a, Random>,
+        Write<'a, EventChannel<GameEvent>>,
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            clock,
+            pos_storage,
+            rot_storage,
+            vel_storage,
+            mut weapon_storage,
+            mut random,
+            mut events,
+            entities,
+            updater,
+        ): Self::SystemData,
+    ) {
+        for (entity, pos, rot, vel, weapon) in (
+            &*entities,
+            &pos_storage,
+            rot_storage.maybe(),
+            vel_storage.maybe(),
+            &mut weapon_storage,
+        )
+            .join()
+        {
+            if !weapon.firing || clock.time < weapon.ready_at {
+                continue;
+            }
+            weapon.ready_at = clock.time + weapon.cooldown();
+
+            let projectile = game::create_projectile(
+                updater.create_entity(&entities),
+                entity,
+                weapon,
+                pos.0,
+                rot.map_or(0.0, |r| r.0),
+                vel.map_or(Vector::default(), |v| v.0),
+                &mut random,
+            ).build();
+            events.single_write(GameEvent::Fired {
+                shooter: entity,
+                projectile,
+            });
+        }
+    }
+}
+
+pub struct LifetimeSys;
+impl<'a> System<'a> for LifetimeSys {
+    type SystemData = (Read<'a, Clock>, WriteStorage<'a, Lifetime>, Entities<'a>);
+
+    fn run(&mut self, (clock, mut lifetime_storage, entities): Self::SystemData) {
+        for (entity, lifetime) in (&*entities, &mut lifetime_storage).join() {
+            lifetime.0 -= clock.delta;
+            if lifetime.0 <= 0.0 {
+                if let Err(e) = entities.delete(entity) {
+                    println!("Failed to delete expired entity: {:?}", e);
+                }
+            }
+        }
     }
 }
 
@@ -77,15 +428,75 @@ impl<'a> System<'a> for AccelerationSys {
     type SystemData = (
         Read<'a, Clock>,
         ReadStorage<'a, Acceleration>,
+        ReadStorage<'a, FlightModel>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Rotation>,
+        WriteStorage<'a, Force>,
         WriteStorage<'a, Velocity>,
+        WriteStorage<'a, AngularVelocity>,
     );
 
-    fn run(&mut self, (clock_storage, acc_storage, mut vel_storage): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            clock_storage,
+            acc_storage,
+            flight_storage,
+            mass_storage,
+            rot_storage,
+            mut force_storage,
+            mut vel_storage,
+            mut angular_storage,
+        ): Self::SystemData,
+    ) {
         let dt = (*clock_storage).delta;
-        for (acc, vel) in (&acc_storage, &mut vel_storage).join() {
-            vel.0.dx += acc.0.dx * dt;
-            vel.0.dy += acc.0.dy * dt;
+        for (acc, vel, flight) in (&acc_storage, &mut vel_storage, flight_storage.maybe()).join() {
+            match flight {
+                Some(flight) => vel.0 = flight.integrate(vel.0, acc.0, dt),
+                None => {
+                    vel.0.dx += acc.0.dx * dt;
+                    vel.0.dy += acc.0.dy * dt;
+                }
+            }
+        }
+
+        // drag slows entities down even when they don't accelerate
+        for (vel, flight, ()) in (&mut vel_storage, &flight_storage, !&acc_storage).join() {
+            vel.0 = flight.integrate(vel.0, Vector::default(), dt);
+        }
+        for (angular, flight) in (&mut angular_storage, &flight_storage).join() {
+            angular.0 = flight.integrate_angular(angular.0, dt);
         }
+
+        // forces only push for one tick, bodies without mass yet ignore them
+        for (force, mass, rot, vel, angular) in (
+            &force_storage,
+            &mass_storage,
+            rot_storage.maybe(),
+            (&mut vel_storage).maybe(),
+            (&mut angular_storage).maybe(),
+        )
+            .join()
+        {
+            if mass.mass <= 0.0 {
+                continue;
+            }
+            // bodies turn around their center of mass, which drags their position along
+            let arm = mass.center.rotated(rot.map_or(0.0, |rot| rot.0));
+            let torque = force.torque - arm.perpendicular().dot(force.force);
+            let spin = match angular {
+                Some(angular) if mass.inertia > 0.0 => {
+                    let spin = torque * dt / mass.inertia;
+                    angular.0 += spin;
+                    spin
+                }
+                _ => 0.0,
+            };
+            if let Some(vel) = vel {
+                vel.0 = vel.0 + force.force * (dt / mass.mass) - arm.perpendicular() * spin;
+            }
+        }
+        force_storage.clear();
     }
 }
 
@@ -121,3 +532,481 @@ impl<'a> System<'a> for AngularMovementSys {
         }
     }
 }
+
+pub struct SpatialIndexSys;
+impl<'a> System<'a> for SpatialIndexSys {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Shape>,
+        Write<'a, SpatialGrid>,
+    );
+
+    fn run(&mut self, (entities, pos_storage, shape_storage, mut grid): Self::SystemData) {
+        grid.clear();
+        for (entity, pos, shape) in (&*entities, &pos_storage, &shape_storage).join() {
+            grid.insert(entity, pos.0, shape.bounding_radius());
+        }
+    }
+}
+
+pub struct CollisionSys;
+impl<'a> System<'a> for CollisionSys {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SpatialGrid>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Shape>,
+        Write<'a, Collisions>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, grid, pos_storage, rot_storage, shape_storage, mut collisions): Self::SystemData,
+    ) {
+        collisions.0.clear();
+
+        // transform all shapes into world coords once
+        let bodies: HashMap<_, _> = (
+            &*entities,
+            &pos_storage,
+            rot_storage.maybe(),
+            &shape_storage,
+        )
+            .join()
+            .map(|(entity, pos, rot, shape)| {
+                let rotation = rot.map_or(0.0, |r| r.0);
+                (entity, collision::world_primitives(shape, pos.0, rotation))
+            }).collect();
+
+        // only test pairs with overlapping bounding circles
+        for (a, b) in grid.potential_pairs() {
+            let contact = match (bodies.get(&a), bodies.get(&b)) {
+                (Some(primitives_a), Some(primitives_b)) => {
+                    collision::collide(primitives_a, primitives_b)
+                }
+                _ => None,
+            };
+            if let Some(contact) = contact {
+                collisions.0.push(Collision {
+                    a,
+                    b,
+                    normal: contact.normal,
+                    depth: contact.depth,
+                    point: contact.point,
+                });
+            }
+        }
+    }
+}
+
+// works out the mass of new rigid bodies
+pub struct MassSys;
+impl<'a> System<'a> for MassSys {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, RigidBody>,
+        ReadStorage<'a, Shape>,
+        WriteStorage<'a, Mass>,
+    );
+
+    fn run(&mut self, (entities, body_storage, shape_storage, mut mass_storage): Self::SystemData) {
+        let missing: Vec<_> = (&*entities, &body_storage, &shape_storage, !&mass_storage)
+            .join()
+            .map(|(entity, body, shape, ())| (entity, Mass::new(shape, body)))
+            .collect();
+        for (entity, mass) in missing {
+            if let Err(e) = mass_storage.insert(entity, mass) {
+                println!("Failed to add mass: {:?}", e);
+            }
+        }
+    }
+}
+
+// bounces colliding rigid bodies off each other and pushes them apart
+pub struct CollisionResponseSys;
+impl<'a> System<'a> for CollisionResponseSys {
+    type SystemData = (
+        Read<'a, Collisions>,
+        ReadStorage<'a, RigidBody>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, Rotation>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, AngularVelocity>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            collisions,
+            body_storage,
+            mass_storage,
+            projectile_storage,
+            rot_storage,
+            mut pos_storage,
+            mut vel_storage,
+            mut angular_storage,
+        ): Self::SystemData,
+    ) {
+        for collision in collisions.0.iter() {
+            let (a, b) = (collision.a, collision.b);
+            // projectiles pass through their shooter
+            if friendly(&projectile_storage, a, b) {
+                continue;
+            }
+            let (body_a, body_b) = match (body_storage.get(a), body_storage.get(b)) {
+                (Some(body_a), Some(body_b)) => (body_a, body_b),
+                _ => continue,
+            };
+            let (mut a_body, mut b_body) = match (
+                rigid_body(
+                    a,
+                    &mass_storage,
+                    &rot_storage,
+                    &pos_storage,
+                    &vel_storage,
+                    &angular_storage,
+                ),
+                rigid_body(
+                    b,
+                    &mass_storage,
+                    &rot_storage,
+                    &pos_storage,
+                    &vel_storage,
+                    &angular_storage,
+                ),
+            ) {
+                (Some(a_body), Some(b_body)) => (a_body, b_body),
+                _ => continue,
+            };
+
+            let (restitution, friction) = physics::mix(body_a, body_b);
+            let (push_a, push_b) =
+                physics::resolve(&mut a_body, &mut b_body, collision, restitution, friction);
+            for &(entity, body, push) in &[(a, a_body, push_a), (b, b_body, push_b)] {
+                if let Some(pos) = pos_storage.get_mut(entity) {
+                    if let Some(vel) = vel_storage.get_mut(entity) {
+                        vel.0 = body.velocity_at(pos.0);
+                    }
+                    pos.0 = pos.0 + push;
+                }
+                if let Some(angular) = angular_storage.get_mut(entity) {
+                    angular.0 = body.angular_velocity;
+                }
+            }
+        }
+    }
+}
+
+// bumps can't move entities without a velocity or spin ones without an angular velocity
+// bodies without mass, like lines without width, don't bump into anything
+fn rigid_body(
+    entity: Entity,
+    mass_storage: &ReadStorage<Mass>,
+    rot_storage: &ReadStorage<Rotation>,
+    pos_storage: &WriteStorage<Position>,
+    vel_storage: &WriteStorage<Velocity>,
+    angular_storage: &WriteStorage<AngularVelocity>,
+) -> Option<Body> {
+    let (vel, angular) = (vel_storage.get(entity), angular_storage.get(entity));
+    let mut body = Body::new(
+        pos_storage.get(entity)?.0,
+        rot_storage.get(entity).map_or(0.0, |rot| rot.0),
+        vel.map_or(Vector::default(), |vel| vel.0),
+        angular.map_or(0.0, |angular| angular.0),
+        mass_storage.get(entity).filter(|mass| mass.mass > 0.0)?,
+    );
+    if vel.is_none() {
+        body.inverse_mass = 0.0;
+    }
+    if angular.is_none() {
+        body.inverse_inertia = 0.0;
+    }
+    Some(body)
+}
+
+pub struct DamageSys;
+impl<'a> System<'a> for DamageSys {
+    type SystemData = (
+        Read<'a, Collisions>,
+        ReadStorage<'a, Damage>,
+        ReadStorage<'a, Projectile>,
+        WriteStorage<'a, Health>,
+        Write<'a, EventChannel<GameEvent>>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            collisions,
+            damage_storage,
+            projectile_storage,
+            mut health_storage,
+            mut events,
+            entities,
+        ): Self::SystemData,
+    ) {
+        for collision in collisions.0.iter() {
+            for &(attacker, target) in &[(collision.a, collision.b), (collision.b, collision.a)] {
+                let damage = match damage_storage.get(attacker) {
+                    Some(damage) => damage.0,
+                    None => continue,
+                };
+                // projectiles act on behalf of their shooter
+                let attacker = projectile_storage
+                    .get(attacker)
+                    .and_then(|p| p.owner)
+                    .unwrap_or(attacker);
+                if attacker == target {
+                    continue;
+                }
+                if let Some(health) = health_storage.get_mut(target) {
+                    health.take_damage(damage, attacker);
+                    events.single_write(GameEvent::Hit {
+                        target,
+                        attacker,
+                        damage,
+                    });
+                }
+            }
+        }
+
+        for (entity, health) in (&*entities, &health_storage).join() {
+            if health.is_dead() {
+                events.single_write(GameEvent::Destroyed {
+                    entity,
+                    killer: health.last_attacker,
+                });
+                if let Err(e) = entities.delete(entity) {
+                    println!("Failed to delete destroyed entity: {:?}", e);
+                }
+            }
+        }
+    }
+}
+
+pub struct ProjectileSys;
+impl<'a> System<'a> for ProjectileSys {
+    type SystemData = (
+        Read<'a, Collisions>,
+        ReadStorage<'a, Projectile>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (collisions, projectile_storage, entities): Self::SystemData) {
+        // projectiles disappear on impact with anything but their shooter and its projectiles
+        for collision in collisions.0.iter() {
+            for &(projectile_entity, other) in
+                &[(collision.a, collision.b), (collision.b, collision.a)]
+            {
+                if projectile_storage.get(projectile_entity).is_none() {
+                    continue;
+                }
+                if !friendly(&projectile_storage, projectile_entity, other) {
+                    if let Err(e) = entities.delete(projectile_entity) {
+                        println!("Failed to delete projectile: {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// whether one of `a` and `b` shot the other, or both were shot by the same entity
+fn friendly(projectile_storage: &ReadStorage<Projectile>, a: Entity, b: Entity) -> bool {
+    let owner = |entity| projectile_storage.get(entity).and_then(|p| p.owner);
+    let (owner_a, owner_b) = (owner(a), owner(b));
+    owner_a == Some(b) || owner_b == Some(a) || (owner_a.is_some() && owner_a == owner_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Game, Timestep};
+
+    const MAX_SPEED: f64 = 10.0;
+
+    // the player sits still at the origin
+    fn game() -> Game<'static, 'static> {
+        let mut game = Game::new();
+        game.create_player();
+        game
+    }
+
+    fn spawn_ai(game: &mut Game, steering: Steering, position: Point, sight_range: f64) -> Entity {
+        game.world
+            .create_entity()
+            .with(Position(position))
+            .with(Rotation::default())
+            .with(Velocity::default())
+            .with(AiBehaviour {
+                steering,
+                sight_range,
+                max_speed: MAX_SPEED,
+                max_acceleration: 40.0,
+                wander_angle: 0.0,
+            }).build()
+    }
+
+    fn run(game: &mut Game, seconds: f64) {
+        let dt = Timestep::default().delta();
+        for _ in 0..(seconds / dt).round() as u32 {
+            game.update(dt);
+        }
+    }
+
+    fn position(game: &Game, entity: Entity) -> Point {
+        game.world.read_storage::<Position>().get(entity).unwrap().0
+    }
+
+    fn distance(game: &Game, entity: Entity) -> f64 {
+        (position(game, entity) - Point::new(0.0, 0.0)).length()
+    }
+
+    // angle of the entity as seen from the player
+    fn bearing(game: &Game, entity: Entity) -> f64 {
+        (position(game, entity) - Point::new(0.0, 0.0)).angle()
+    }
+
+    #[test]
+    fn seekers_come_and_fleers_go() {
+        let mut game = game();
+        let seeker = spawn_ai(&mut game, Steering::Seek, Point::new(20.0, 0.0), 50.0);
+        let fleer = spawn_ai(&mut game, Steering::Flee, Point::new(-20.0, 0.0), 50.0);
+        run(&mut game, 1.0);
+        assert!(distance(&game, seeker) < 16.0, "seeker at {}", distance(&game, seeker));
+        assert!(distance(&game, fleer) > 24.0, "fleer at {}", distance(&game, fleer));
+        // straight towards and away from the player
+        assert!(position(&game, seeker).y.abs() < 1e-9);
+        assert!(position(&game, fleer).y.abs() < 1e-9);
+    }
+
+    #[test]
+    fn orbiters_and_strafers_circle_at_their_distance() {
+        let mut game = game();
+        let orbiter = spawn_ai(
+            &mut game,
+            Steering::Orbit { distance: 10.0 },
+            Point::new(10.0, 0.0),
+            50.0,
+        );
+        let strafer = spawn_ai(
+            &mut game,
+            Steering::Strafe { distance: 10.0 },
+            Point::new(-10.0, 0.0),
+            50.0,
+        );
+        let bearings = (bearing(&game, orbiter), bearing(&game, strafer));
+        for _ in 0..30 {
+            run(&mut game, 0.1);
+            for &entity in &[orbiter, strafer] {
+                let distance = distance(&game, entity);
+                // they drift out a bit while turning, until the correction holds them
+                assert!(distance > 9.0 && distance < 15.0, "{:?} at {}", entity, distance);
+            }
+        }
+        // got some way around
+        assert!((bearing(&game, orbiter) - bearings.0).abs() > 1.0);
+        assert!((bearing(&game, strafer) - bearings.1).abs() > 1.0);
+
+        // strafers face the player, orbiters the way around it
+        let rot_storage = game.world.read_storage::<Rotation>();
+        let to_player = Point::new(0.0, 0.0) - position(&game, strafer);
+        let off = (rot_storage.get(strafer).unwrap().0 - to_player.angle()).sin();
+        assert!(off.abs() < 0.1, "strafer looks {} off", off);
+        // mostly sideways, a little inwards to hold the distance
+        let to_player = Point::new(0.0, 0.0) - position(&game, orbiter);
+        let off = (rot_storage.get(orbiter).unwrap().0 - to_player.angle()).cos();
+        assert!(off.abs() < 0.5, "orbiter looks {} towards the player", off);
+    }
+
+    #[test]
+    fn orbits_of_distance_zero_stay_close() {
+        let mut game = game();
+        // some right on top of the player
+        let mut entities = Vec::new();
+        for &position in &[Point::new(3.0, 0.0), Point::new(0.0, 0.0)] {
+            let orbit = Steering::Orbit { distance: 0.0 };
+            entities.push(spawn_ai(&mut game, orbit, position, 50.0));
+            let strafe = Steering::Strafe { distance: 0.0 };
+            entities.push(spawn_ai(&mut game, strafe, position, 50.0));
+        }
+        run(&mut game, 2.0);
+        for &entity in &entities {
+            let position = position(&game, entity);
+            assert!(position.x.is_finite() && position.y.is_finite(), "{:?}", position);
+            assert!(distance(&game, entity) < 3.0, "{:?} at {:?}", entity, position);
+        }
+    }
+
+    #[test]
+    fn out_of_sight_players_are_ignored() {
+        let mut game = game();
+        // wanders off to the right, away from the player
+        let blind = spawn_ai(&mut game, Steering::Seek, Point::new(20.0, 0.0), 5.0);
+        let wanderer = spawn_ai(&mut game, Steering::Wander, Point::new(10.0, 0.0), 50.0);
+        // far away, but it can see that far
+        let sharp = spawn_ai(&mut game, Steering::Seek, Point::new(-60.0, 0.0), 100.0);
+        run(&mut game, 1.0);
+
+        assert!(distance(&game, blind) > 20.0);
+        assert!(distance(&game, wanderer) > 10.0);
+        assert!(distance(&game, sharp) < 56.0, "seeker at {}", distance(&game, sharp));
+        let ai_storage = game.world.read_storage::<AiBehaviour>();
+        let vel_storage = game.world.read_storage::<Velocity>();
+        for &entity in &[blind, wanderer] {
+            assert!(ai_storage.get(entity).unwrap().wander_angle != 0.0);
+            let speed = vel_storage.get(entity).unwrap().0.length();
+            assert!(speed <= MAX_SPEED / 2.0 + 1e-9, "{:?} wanders at {}", entity, speed);
+        }
+    }
+
+    #[test]
+    fn waves_spawn_on_time_and_skip_missing_prefabs() {
+        let waves = r#"[
+            (
+                trigger: At(1.0),
+                groups: [
+                    (count: 3, prefab: "seeker", formation: Ring(radius: 30.0)),
+                    (count: 2, prefab: "nope", formation: Edges),
+                ],
+            ),
+            (
+                trigger: At(2.0),
+                groups: [
+                    (count: 4, prefab: "strafer", formation: Line(distance: 35.0, spacing: 3.0)),
+                ],
+            ),
+        ]"#;
+        let mut game = game();
+        game.add_resource(WaveDirector::new(ron::de::from_str(waves).unwrap()));
+        let enemies = |game: &Game| game.world.read_storage::<AiBehaviour>().join().count();
+
+        // the time each wave started at
+        let mut started = Vec::new();
+        let dt = Timestep::default().delta();
+        for _ in 0..(2.5 / dt) as u32 {
+            game.update(dt);
+            let time = game.world.read_resource::<Clock>().time;
+            for event in game.events() {
+                if let GameEvent::WaveStarted { wave } = *event {
+                    started.push((wave, time));
+                }
+            }
+            let expected = match started.len() {
+                0 => 0,
+                1 => 3,
+                _ => 7,
+            };
+            assert_eq!(enemies(&game), expected, "at {} s", time);
+        }
+        assert_eq!(started.iter().map(|&(wave, _)| wave).collect::<Vec<_>>(), vec![0, 1]);
+        for (&(wave, time), &at) in started.iter().zip(&[1.0, 2.0]) {
+            assert!(time >= at && time < at + 2.0 * dt, "wave {} at {} s", wave, time);
+        }
+    }
+}
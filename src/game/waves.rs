@@ -0,0 +1,171 @@
+use game::components::*;
+use specs::Entity;
+use std::f64::consts::PI;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Wave {
+    pub trigger: WaveTrigger,
+    pub groups: Vec<SpawnGroup>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum WaveTrigger {
+    // at the given world time
+    At(f64),
+
+    // this many s after every enemy from the previous waves is destroyed
+    Cleared(f64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpawnGroup {
+    pub count: usize,
+    // name of the prefab to spawn
+    pub prefab: String,
+    pub formation: Formation,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Formation {
+    // evenly spaced circle around the player
+    Ring { radius: f64 },
+
+    // a line facing the player from a random direction
+    Line { distance: f64, spacing: f64 },
+
+    // random points just outside the visible area
+    Edges,
+}
+
+// spawns waves of enemies, loaded with `Game::load_waves`
+#[derive(Default)]
+pub struct WaveDirector {
+    waves: Vec<Wave>,
+    next_wave: usize,
+
+    // enemies spawned so far, checked by `WaveTrigger::Cleared`
+    spawned: Vec<Entity>,
+
+    // world time at which the last of `spawned` was gone
+    cleared_at: Option<f64>,
+}
+
+impl WaveDirector {
+    pub fn new(waves: Vec<Wave>) -> Self {
+        WaveDirector {
+            waves,
+            ..Default::default()
+        }
+    }
+
+    // index of the wave that should spawn now, if any
+    // `is_alive` tells which of the spawned enemies are still around
+    pub fn due_wave<F>(&mut self, time: f64, is_alive: F) -> Option<usize>
+    where
+        F: Fn(Entity) -> bool,
+    {
+        self.spawned.retain(|&e| is_alive(e));
+        if self.spawned.is_empty() {
+            self.cleared_at.get_or_insert(time);
+        }
+
+        let due = match self.waves.get(self.next_wave)?.trigger {
+            WaveTrigger::At(at) => time >= at,
+            WaveTrigger::Cleared(delay) => match self.cleared_at {
+                Some(cleared_at) => time >= cleared_at + delay,
+                None => false,
+            },
+        };
+        if due {
+            self.next_wave += 1;
+            Some(self.next_wave - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn wave(&self, index: usize) -> &Wave {
+        &self.waves[index]
+    }
+
+    pub fn track(&mut self, entity: Entity) {
+        self.spawned.push(entity);
+        self.cleared_at = None;
+    }
+}
+
+// `WaveDirector` with entities replaced by save markers
+#[derive(Serialize, Deserialize)]
+pub struct WaveDirectorData<M> {
+    waves: Vec<Wave>,
+    next_wave: usize,
+    spawned: Vec<M>,
+    cleared_at: Option<f64>,
+}
+
+impl WaveDirector {
+    pub fn to_data<M, F>(&self, mut ids: F) -> WaveDirectorData<M>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        WaveDirectorData {
+            waves: self.waves.clone(),
+            next_wave: self.next_wave,
+            spawned: self.spawned.iter().filter_map(|&e| ids(e)).collect(),
+            cleared_at: self.cleared_at,
+        }
+    }
+
+    pub fn from_data<M, F>(data: WaveDirectorData<M>, ids: F) -> Self
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        WaveDirector {
+            waves: data.waves,
+            next_wave: data.next_wave,
+            spawned: data.spawned.into_iter().filter_map(ids).collect(),
+            cleared_at: data.cleared_at,
+        }
+    }
+}
+
+// spawn points for `count` entities around `center`
+// `view_size` is the visible area in world coords
+pub fn formation_positions(
+    formation: Formation,
+    count: usize,
+    center: Point,
+    view_size: Vector,
+    random: &mut Random,
+) -> Vec<Point> {
+    match formation {
+        Formation::Ring { radius } => (0..count)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f64 / count as f64;
+                center + Vector::new(radius, 0.0).rotated(angle)
+            }).collect(),
+        Formation::Line { distance, spacing } => {
+            let direction = Vector::new(1.0, 0.0).rotated(random.range(0.0, 2.0 * PI));
+            let middle = center + direction * distance;
+            let start = -(count as f64 - 1.0) / 2.0;
+            (0..count)
+                .map(|i| middle + direction.perpendicular() * ((start + i as f64) * spacing))
+                .collect()
+        }
+        Formation::Edges => {
+            // a bit of margin so nothing pops up on screen
+            let half = view_size * 0.5 + Vector::new(2.0, 2.0);
+            (0..count)
+                .map(|_| {
+                    let along = random.range(-1.0, 1.0);
+                    let offset = match random.range(0.0, 4.0) as u8 {
+                        0 => Vector::new(-half.dx, along * half.dy),
+                        1 => Vector::new(half.dx, along * half.dy),
+                        2 => Vector::new(along * half.dx, -half.dy),
+                        _ => Vector::new(along * half.dx, half.dy),
+                    };
+                    center + offset
+                }).collect()
+        }
+    }
+}
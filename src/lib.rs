@@ -0,0 +1,15 @@
+// the game itself, frontends in main.rs drive and draw it
+extern crate nalgebra as na;
+extern crate png;
+extern crate rand;
+extern crate ron;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate shrev;
+extern crate specs;
+#[macro_use]
+extern crate specs_derive;
+
+pub mod consts;
+pub mod game;
@@ -1,22 +1,71 @@
 // all commonly used crates should be here
-extern crate nalgebra as na;
+extern crate deasil;
+// only the headless frontend reads scripts and touches the world directly
+#[cfg(feature = "headless")]
 extern crate rand;
-extern crate specs;
+#[cfg(feature = "headless")]
+extern crate serde;
+#[cfg(feature = "headless")]
 #[macro_use]
-extern crate specs_derive;
+extern crate serde_derive;
+#[cfg(feature = "headless")]
+extern crate specs;
+
+use std::env;
+use std::process;
 
-// shared modules
-mod consts;
-mod game;
+// frontend without a window, for CI and replays
+#[cfg(feature = "headless")]
+mod frontend_headless;
 
 // Piston frontend
+#[cfg(feature = "piston")]
 mod frontend_piston;
+
+// SDL2 frontend
+#[cfg(feature = "sdl")]
+mod frontend_sdl;
+
+// a frontend's name and its main function
+// which gets the command line arguments after the frontend choice
+type Frontend = (&'static str, fn(&[String]));
+
+// compiled in frontends, the first one is the default
+const FRONTENDS: &[Frontend] = &[
+    #[cfg(feature = "piston")]
+    ("piston", |_| frontend_piston::main()),
+    #[cfg(feature = "sdl")]
+    ("sdl", |_| frontend_sdl::main()),
+    #[cfg(feature = "headless")]
+    ("headless", frontend_headless::main),
+];
+
 fn main() {
-    frontend_piston::main()
+    let args: Vec<String> = env::args().collect();
+    let (name, args) = match args.get(1).map(String::as_str) {
+        Some("--frontend") => match args.get(2) {
+            Some(name) => (Some(name.as_str()), &args[3..]),
+            None => exit_with_usage("--frontend needs a value"),
+        },
+        _ => (None, args.get(1..).unwrap_or(&[])),
+    };
+
+    let frontend = match name {
+        Some(name) => FRONTENDS.iter().find(|&&(n, _)| n == name),
+        None => FRONTENDS.first(),
+    };
+    match frontend {
+        Some(&(_, main)) => main(args),
+        None if FRONTENDS.is_empty() => {
+            println!("No frontend compiled in, enable the piston, sdl or headless feature");
+            process::exit(1);
+        }
+        None => exit_with_usage(&format!("unknown frontend {}", name.unwrap_or_default())),
+    }
 }
 
-// SDL2 frontend
-// mod frontend_sdl;
-// fn main() {
-//     frontend_sdl::main()
-// }
+fn exit_with_usage(error: &str) -> ! {
+    let names: Vec<_> = FRONTENDS.iter().map(|&(name, _)| name).collect();
+    println!("{}\nusage: deasil [--frontend {}] [frontend options]", error, names.join("|"));
+    process::exit(2);
+}